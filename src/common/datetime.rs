@@ -1,8 +1,11 @@
 use chrono::offset::FixedOffset;
 use chrono::prelude::Utc;
 
+use chrono::Date;
 use chrono::DateTime;
 use chrono::Datelike;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
 use chrono::Timelike;
 use chrono::Weekday;
 
@@ -21,20 +24,92 @@ pub fn get_day_of_week() -> Weekday {
     get_datetime().date().weekday()
 }
 
+// How dates are passed between the clients and the server
+pub static DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub fn format_date(date: Date<FixedOffset>) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
+
+pub fn parse_date(date: &str) -> Option<Date<FixedOffset>> {
+    let naive_date = NaiveDate::parse_from_str(date.trim(), DATE_FORMAT).ok()?;
+    Some(Date::from_utc(naive_date, *get_datetime().offset()))
+}
+
+// Turns "today", "tomorrow", "yesterday" or a weekday like "friday" or "fri" into a date
+//  A weekday means the next time that day comes up, so "friday" on a Friday is today
+pub fn get_date_from_name(name: &str) -> Option<Date<FixedOffset>> {
+    let today = get_datetime().date();
+    match name.to_lowercase().as_str() {
+        "today" => Some(today),
+        "tomorrow" => Some(today + chrono::Duration::days(1)),
+        "yesterday" => Some(today - chrono::Duration::days(1)),
+        name => {
+            let weekday = name.parse::<Weekday>().ok()?;
+            let days_ahead =
+                (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+            Some(today + chrono::Duration::days(i64::from(days_ahead)))
+        }
+    }
+}
+
+// Turns a time like "9pm", "9:30 am", "21:00", "noon" or "midnight" into a time of day
+pub fn parse_time(time: &str) -> Option<NaiveTime> {
+    let time: String = time
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '.')
+        .collect();
+
+    let (time, is_pm) = match time.as_str() {
+        "noon" => return Some(NaiveTime::from_hms(12, 0, 0)),
+        "midnight" => return Some(NaiveTime::from_hms(0, 0, 0)),
+        time if time.ends_with("pm") => (&time[..time.len() - 2], Some(true)),
+        time if time.ends_with("am") => (&time[..time.len() - 2], Some(false)),
+        time => (time, None),
+    };
+
+    let mut parts = time.splitn(2, ':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = match parts.next() {
+        Some(minute) => minute.parse().ok()?,
+        None => 0,
+    };
+
+    let hour = match is_pm {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some(true) if hour < 12 => hour + 12,
+        Some(false) if hour == 12 => 0,
+        _ => hour,
+    };
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+// Like "9:30 PM"
+pub fn format_time(time: NaiveTime) -> String {
+    time.format("%-I:%M %p").to_string()
+}
+
 static HOUR_TO_RUN_AT: u32 = 5;
 
 // Runs at (HOUR_TO_RUN_AT + 1) in summer or HOUR_TO_RUN_AT in winter
 pub fn get_time_till_scheduled() -> std::time::Duration {
+    get_time_till(HOUR_TO_RUN_AT)
+}
+
+// Time until the next time it's the start of the given hour
+pub fn get_time_till(hour: u32) -> std::time::Duration {
     let current_time = get_datetime();
 
     // We want to do it today if it has yet to happen, or else tomorrow
-    let next_run_date = if current_time.time().hour() < HOUR_TO_RUN_AT {
+    let next_run_date = if current_time.time().hour() < hour {
         current_time.date()
     } else {
         current_time.date() + chrono::Duration::days(1)
     };
 
-    let next_run = next_run_date.and_hms(HOUR_TO_RUN_AT, 0, 0);
+    let next_run = next_run_date.and_hms(hour, 0, 0);
 
     (next_run - current_time).to_std().unwrap()
 }
@@ -6,6 +6,7 @@ pub enum UMassBotError {
     RequestError(reqwest::Error),
     IoError(std::io::Error),
     SerenityError(serenity::Error),
+    JsonError(serde_json::Error),
 }
 
 pub type Result<T> = std::result::Result<T, UMassBotError>;
@@ -16,6 +17,7 @@ impl fmt::Display for UMassBotError {
             UMassBotError::RequestError(ref err) => write!(f, "Request error: {}", err),
             UMassBotError::IoError(ref err) => write!(f, "IO error: {}", err),
             UMassBotError::SerenityError(ref err) => write!(f, "Discord error: {}", err),
+            UMassBotError::JsonError(ref err) => write!(f, "JSON error: {}", err),
         }
     }
 }
@@ -26,6 +28,7 @@ impl error::Error for UMassBotError {
             UMassBotError::RequestError(ref err) => err.description(),
             UMassBotError::IoError(ref err) => err.description(),
             UMassBotError::SerenityError(ref err) => err.description(),
+            UMassBotError::JsonError(ref err) => err.description(),
         }
     }
 
@@ -34,6 +37,7 @@ impl error::Error for UMassBotError {
             UMassBotError::RequestError(ref err) => Some(err),
             UMassBotError::IoError(ref err) => Some(err),
             UMassBotError::SerenityError(ref err) => Some(err),
+            UMassBotError::JsonError(ref err) => Some(err),
         }
     }
 }
@@ -55,3 +59,9 @@ impl From<std::io::Error> for UMassBotError {
         UMassBotError::IoError(err)
     }
 }
+
+impl From<serde_json::Error> for UMassBotError {
+    fn from(err: serde_json::Error) -> UMassBotError {
+        UMassBotError::JsonError(err)
+    }
+}
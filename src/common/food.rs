@@ -0,0 +1,376 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fmt;
+
+use crate::datetime::{get_datetime, parse_date};
+
+// A dish that matched a food search, along with its diet tags and allergens
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct DishMatch {
+    pub name: String,
+    pub diets: Vec<String>,
+    pub allergens: Vec<String>,
+    // Out of five stars, from the bots' users' ratings of the dish at this dining common
+    #[serde(default)]
+    pub average_rating: Option<f64>,
+    #[serde(default)]
+    pub ratings: usize,
+}
+
+impl DishMatch {
+    // The dish's name, with its average rating if it has one
+    pub fn label(&self) -> String {
+        match self.average_rating {
+            Some(average) => format!("{} ({:.1}/5)", self.name, average),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// The dishes that matched at one dining common and meal, best matches first
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct MenuMatch {
+    pub dining_common: String,
+    pub meal: String,
+    pub dishes: Vec<DishMatch>,
+}
+
+impl MenuMatch {
+    pub fn dish_names(&self) -> Vec<&str> {
+        self.dishes.iter().map(|dish| dish.name.as_str()).collect()
+    }
+}
+
+// The results of a food search, with the places it was found ordered by how well they matched
+//  An empty list of places means that it was searched for but not found
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct FoodSearch {
+    pub food: String,
+    pub diets: Vec<String>,
+    pub excluded_allergens: Vec<String>,
+    // In the same format as the date query parameter
+    pub date: String,
+    pub found: Vec<MenuMatch>,
+    // Dining commons whose menus couldn't be fetched, so weren't searched
+    #[serde(default)]
+    pub unavailable: Vec<String>,
+    // Set when the date is past when the dining site has menus for, so nothing was searched
+    #[serde(default)]
+    pub too_far_ahead: bool,
+    // Set when the search was narrowed to one dining common or meal
+    #[serde(default)]
+    pub dining_common: Option<String>,
+    #[serde(default)]
+    pub meal: Option<String>,
+}
+
+impl FoodSearch {
+    // What was searched for, like "pizza (vegan, no peanuts) at Berk Dinner on Friday, October 17"
+    pub fn description(&self) -> String {
+        let mut description = self.food.clone();
+
+        let filters: Vec<String> = self
+            .diets
+            .iter()
+            .cloned()
+            .chain(
+                self.excluded_allergens
+                    .iter()
+                    .map(|allergen| format!("no {}", allergen)),
+            )
+            .collect();
+        if !filters.is_empty() {
+            description = format!("{} ({})", description, filters.join(", "));
+        }
+
+        let place: Vec<&str> = self
+            .dining_common
+            .iter()
+            .chain(self.meal.iter())
+            .map(|name| name.as_str())
+            .collect();
+        if !place.is_empty() {
+            description = format!("{} at {}", description, place.join(" "));
+        }
+
+        if let Some(date) = parse_date(&self.date) {
+            if date != get_datetime().date() {
+                description = format!("{} on {}", description, date.format("%A, %B %-d"));
+            }
+        }
+
+        description
+    }
+
+    // A note about any dining commons that couldn't be searched
+    pub fn unavailable_note(&self) -> Option<String> {
+        if self.too_far_ahead {
+            Some("Menus aren't posted that far ahead yet".to_string())
+        } else if self.unavailable.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "Couldn't get the menus for {}",
+                self.unavailable.join(", ")
+            ))
+        }
+    }
+}
+
+impl fmt::Display for FoodSearch {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.found.is_empty() {
+            write!(formatter, "{} not found", self.description())?;
+        } else {
+            let places: Vec<String> = self
+                .found
+                .iter()
+                .map(|place| {
+                    let dishes: Vec<String> =
+                        place.dishes.iter().map(|dish| dish.label()).collect();
+                    format!(
+                        "{} {}: {}",
+                        place.dining_common,
+                        place.meal,
+                        dishes.join(", ")
+                    )
+                })
+                .collect();
+
+            write!(formatter, "{}: \n{}", self.description(), places.join("\n"))?;
+        }
+
+        match self.unavailable_note() {
+            Some(note) => write!(formatter, "\n{}", note),
+            None => Ok(()),
+        }
+    }
+}
+
+// The foods that one of the dining commons' meals has, for a digest
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestPlace {
+    pub dining_common: String,
+    pub meal: String,
+    // Each food that was found, along with the dishes it matched
+    pub foods: Vec<(String, Vec<String>)>,
+}
+
+impl DigestPlace {
+    pub fn foods_description(&self) -> String {
+        let foods: Vec<String> = self
+            .foods
+            .iter()
+            .map(|(food, dishes)| format!("{} ({})", food, dishes.join(", ")))
+            .collect();
+        foods.join(", ")
+    }
+}
+
+// Everything a channel has registered for, grouped by where it's being served, so it can all be
+//  sent in one message
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Digest {
+    pub places: Vec<DigestPlace>,
+    pub not_found: Vec<String>,
+    // Foods that couldn't be checked at all
+    pub failed: Vec<String>,
+    pub unavailable: Vec<String>,
+}
+
+impl Digest {
+    pub fn add(&mut self, search: &FoodSearch) {
+        if search.found.is_empty() {
+            self.not_found.push(search.description());
+        }
+
+        for found in &search.found {
+            let dishes: Vec<String> = found
+                .dish_names()
+                .iter()
+                .map(|dish| dish.to_string())
+                .collect();
+            let food = (search.description(), dishes);
+
+            match self.places.iter_mut().find(|place| {
+                place.dining_common == found.dining_common && place.meal == found.meal
+            }) {
+                Some(place) => place.foods.push(food),
+                None => self.places.push(DigestPlace {
+                    dining_common: found.dining_common.clone(),
+                    meal: found.meal.clone(),
+                    foods: vec![food],
+                }),
+            }
+        }
+
+        for dining_common in &search.unavailable {
+            if !self.unavailable.contains(dining_common) {
+                self.unavailable.push(dining_common.clone());
+            }
+        }
+    }
+
+    pub fn add_failed(&mut self, food: &str) {
+        self.failed.push(food.to_string());
+    }
+
+    pub fn heading(&self) -> &'static str {
+        if self.places.is_empty() {
+            "None of your favorites are being served today"
+        } else {
+            "Your favorites today:"
+        }
+    }
+
+    // The lines after the places, about anything that wasn't found or couldn't be checked
+    pub fn notes(&self) -> Vec<String> {
+        let mut notes: Vec<String> = vec![];
+        if !self.places.is_empty() && !self.not_found.is_empty() {
+            notes.push(format!("Not on the menus: {}", self.not_found.join(", ")));
+        }
+        if !self.failed.is_empty() {
+            notes.push(format!("Couldn't check for {}", self.failed.join(", ")));
+        }
+        if !self.unavailable.is_empty() {
+            notes.push(format!(
+                "Couldn't get the menus for {}",
+                self.unavailable.join(", ")
+            ));
+        }
+        notes
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.heading())?;
+        for place in &self.places {
+            write!(
+                formatter,
+                "\n{} {}: {}",
+                place.dining_common,
+                place.meal,
+                place.foods_description()
+            )?;
+        }
+
+        for note in self.notes() {
+            write!(formatter, "\n{}", note)?;
+        }
+        Ok(())
+    }
+}
+
+// Dishes that were added to or removed from a menu after it was first published
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct MenuChange {
+    // Changes are numbered in the order they were noticed
+    pub id: u64,
+    // In the same format as the date query parameter
+    pub date: String,
+    pub dining_common: String,
+    pub meal: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl MenuChange {
+    pub fn describe(&self) -> String {
+        let place = match parse_date(&self.date) {
+            Some(date) if date != get_datetime().date() => format!(
+                "{} {} on {}",
+                self.dining_common,
+                self.meal,
+                date.format("%A, %B %-d")
+            ),
+            _ => format!("{} {}", self.dining_common, self.meal),
+        };
+
+        let mut lines: Vec<String> = vec![];
+        if !self.added.is_empty() {
+            lines.push(format!("Now at {}: {}", place, self.added.join(", ")));
+        }
+        if !self.removed.is_empty() {
+            lines.push(format!(
+                "No longer at {}: {}",
+                place,
+                self.removed.join(", ")
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+// The menu changes matching a food, along with the id that the next change will have
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct MenuChanges {
+    pub latest: u64,
+    pub changes: Vec<MenuChange>,
+}
+
+// Nutrition facts for a single dish, taken from its nutrition lightbox on the dining site
+//  Amounts are in grams unless noted otherwise
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Nutrition {
+    pub dish: String,
+    pub serving_size: Option<String>,
+    pub calories: Option<f64>,
+    pub total_fat: Option<f64>,
+    pub saturated_fat: Option<f64>,
+    // In milligrams
+    pub cholesterol: Option<f64>,
+    // In milligrams
+    pub sodium: Option<f64>,
+    pub total_carbs: Option<f64>,
+    pub fiber: Option<f64>,
+    pub sugars: Option<f64>,
+    pub protein: Option<f64>,
+    pub ingredients: Option<String>,
+}
+
+// Pulls the number out of an amount from the dining site, like "12.5g" or "250"
+pub fn parse_amount(amount: &str) -> Option<f64> {
+    let number: String = amount
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+
+    number.parse::<f64>().ok()
+}
+
+fn write_amount(
+    formatter: &mut fmt::Formatter,
+    name: &str,
+    amount: Option<f64>,
+    unit: &str,
+) -> fmt::Result {
+    match amount {
+        Some(amount) => writeln!(formatter, "{}: {}{}", name, amount, unit),
+        None => Ok(()),
+    }
+}
+
+impl fmt::Display for Nutrition {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(formatter, "{}", self.dish)?;
+        if let Some(serving_size) = &self.serving_size {
+            writeln!(formatter, "Serving size: {}", serving_size)?;
+        }
+        write_amount(formatter, "Calories", self.calories, "")?;
+        write_amount(formatter, "Fat", self.total_fat, "g")?;
+        write_amount(formatter, "Saturated fat", self.saturated_fat, "g")?;
+        write_amount(formatter, "Cholesterol", self.cholesterol, "mg")?;
+        write_amount(formatter, "Sodium", self.sodium, "mg")?;
+        write_amount(formatter, "Carbs", self.total_carbs, "g")?;
+        write_amount(formatter, "Fiber", self.fiber, "g")?;
+        write_amount(formatter, "Sugars", self.sugars, "g")?;
+        write_amount(formatter, "Protein", self.protein, "g")?;
+        if let Some(ingredients) = &self.ingredients {
+            write!(formatter, "Ingredients: {}", ingredients)?;
+        }
+        Ok(())
+    }
+}
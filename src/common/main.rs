@@ -1,3 +1,5 @@
 pub mod datetime;
 pub mod error;
+pub mod food;
 pub mod listeners;
+pub mod query;
@@ -0,0 +1,319 @@
+use chrono::offset::FixedOffset;
+use chrono::Date;
+
+use crate::datetime::{format_date, get_date_from_name};
+
+// A food search as typed into one of the bots, like "chicken +halal -peanuts tomorrow"
+//  "+tag" only shows dishes with that diet tag (vegan, vegetarian, halal, ...)
+//  "-allergen" hides dishes containing that allergen (peanuts, nuts, milk, ...)
+//  "today", "tomorrow" or a weekday name searches that day's menus instead of today's
+//  "@hall meal" at the end only searches that dining common and/or meal, like "@berk dinner",
+//  "@worcester" or "@late night"
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoodQuery {
+    pub food: String,
+    pub diets: Vec<String>,
+    pub excluded_allergens: Vec<String>,
+    pub date: Option<Date<FixedOffset>>,
+    pub dining_common: Option<String>,
+    pub meal: Option<String>,
+}
+
+// Words that start a meal's name, so "@late night" is a meal rather than a dining common
+static MEAL_WORDS: &[&str] = &[
+    "breakfast",
+    "brunch",
+    "lunch",
+    "dinner",
+    "late",
+    "latenight",
+    "grab",
+    "grabngo",
+];
+
+// Lowercases and strips everything but letters and numbers, so "Gluten-Free", "gluten free"
+// and "gluten_free" are all treated as the same
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+fn is_meal(words: &[&str]) -> bool {
+    words
+        .first()
+        .map_or(false, |word| MEAL_WORDS.contains(&normalize(word).as_str()))
+}
+
+impl FoodQuery {
+    pub fn parse(input: &str) -> FoodQuery {
+        let mut food: Vec<&str> = vec![];
+        let mut diets: Vec<String> = vec![];
+        let mut excluded_allergens: Vec<String> = vec![];
+        let mut date: Option<Date<FixedOffset>> = None;
+        // Everything after the "@"
+        let mut place: Option<Vec<&str>> = None;
+
+        // Only the last word can be the date, so foods like "sun dried tomatoes" stay foods
+        let mut words: Vec<&str> = input.split_whitespace().collect();
+        if let Some(day) = words.last().and_then(|word| get_date_from_name(word)) {
+            date = Some(day);
+            words.pop();
+        }
+
+        for word in words {
+            if word.len() > 1 && word.starts_with('+') {
+                diets.push(word[1..].to_string());
+            } else if word.len() > 1 && word.starts_with('-') {
+                excluded_allergens.push(word[1..].to_string());
+            } else if word.starts_with('@') {
+                place = Some(vec![&word[1..]]);
+            } else if let Some(place) = place.as_mut() {
+                place.push(word);
+            } else {
+                food.push(word);
+            }
+        }
+
+        let place: Vec<&str> = place
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|word| !word.is_empty())
+            .collect();
+        // Only the first word is taken as the dining common, and the server moves any more of its
+        //  name, like the "wall" in "blue wall", back over from the meal
+        let (dining_common, meal) = match place.split_first() {
+            None => (None, None),
+            Some(_) if is_meal(&place) => (None, Some(place.join(" "))),
+            Some((dining_common, [])) => (Some(dining_common.to_string()), None),
+            Some((dining_common, meal)) => (Some(dining_common.to_string()), Some(meal.join(" "))),
+        };
+
+        FoodQuery {
+            food: food.join(" "),
+            diets,
+            excluded_allergens,
+            date,
+            dining_common,
+            meal,
+        }
+    }
+
+    // Query parameters for the dining common and meal to look at, if the query was narrowed
+    pub fn place_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![];
+        if let Some(dining_common) = &self.dining_common {
+            params.push(("dining_common", dining_common.clone()));
+        }
+        if let Some(meal) = &self.meal {
+            params.push(("meal", meal.clone()));
+        }
+        params
+    }
+
+    // Query parameters for the diet tags and allergens to filter dishes by
+    pub fn diet_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![];
+        if !self.diets.is_empty() {
+            params.push(("diet", self.diets.join(",")));
+        }
+        if !self.excluded_allergens.is_empty() {
+            params.push(("exclude", self.excluded_allergens.join(",")));
+        }
+        params
+    }
+
+    // Query parameters for the server's food routes
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![("food", self.food.clone())];
+        params.extend(self.diet_params());
+        if let Some(date) = self.date {
+            params.push(("date", format_date(date)));
+        }
+        params.extend(self.place_params());
+        params
+    }
+}
+
+// Splits a day like "tomorrow" or "friday" off of the end of a command, if there is one
+pub fn split_date(input: &str) -> (&str, Option<Date<FixedOffset>>) {
+    let input = input.trim();
+    match input.rfind(' ') {
+        Some(index) => match get_date_from_name(&input[index + 1..]) {
+            Some(date) => (input[..index].trim(), Some(date)),
+            None => (input, None),
+        },
+        None => (input, None),
+    }
+}
+
+// The query parameters for a date, if one was given
+pub fn date_params(date: Option<Date<FixedOffset>>) -> Vec<(&'static str, String)> {
+    date.map(|date| vec![("date", format_date(date))])
+        .unwrap_or_default()
+}
+
+// Query parameters for the server's open route, from input like "at 9pm friday"
+//  No time means right now
+pub fn open_params(input: &str) -> Vec<(&'static str, String)> {
+    let (time, date) = match get_date_from_name(input.trim()) {
+        Some(date) => ("", Some(date)),
+        None => split_date(input),
+    };
+    let time = time.trim();
+    let time = if time.starts_with("at ") {
+        time[3..].trim()
+    } else {
+        time
+    };
+
+    let mut params = date_params(date);
+    if !time.is_empty() && time != "now" {
+        params.push(("at", time.to_string()));
+    }
+    params
+}
+
+// Query parameters for the server's room schedule route, from input like "LGRT 141" or
+//  "LGRT 141 on tuesday"
+pub fn room_schedule_params(input: &str) -> Vec<(&'static str, String)> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    match words.len() {
+        length if length > 2 && words[length - 2] == "on" => vec![
+            ("room", words[..length - 2].join(" ")),
+            ("day", words[length - 1].to_string()),
+        ],
+        _ => vec![("room", words.join(" "))],
+    }
+}
+
+// Query parameters for the server's room status route, from input like "LGRT 141 free? at 2pm"
+//  None unless it's asking whether the room is free
+pub fn room_status_params(input: &str) -> Option<Vec<(&'static str, String)>> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let free = words
+        .iter()
+        .rposition(|word| *word == "free?" || *word == "free")?;
+    if free == 0 {
+        return None;
+    }
+
+    let mut params = vec![("room", words[..free].join(" "))];
+    params.extend(open_params(&words[free + 1..].join(" ")));
+    Some(params)
+}
+
+// Query parameters for the server's free rooms route, from input like "lgrt at 2pm friday for 2
+//  hours". No building means every building, and no time means right now
+pub fn free_room_params(input: &str) -> Vec<(&'static str, String)> {
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let mut params = vec![];
+
+    if words.len() >= 3 && words[words.len() - 3] == "for" {
+        let amount = words[words.len() - 2].parse::<i64>().ok();
+        let unit = words[words.len() - 1].to_lowercase();
+        let minutes = match amount {
+            Some(amount) if unit.starts_with("min") => Some(amount),
+            Some(amount) if unit.starts_with("hour") || unit.starts_with("hr") => Some(amount * 60),
+            _ => None,
+        };
+        if let Some(minutes) = minutes {
+            params.push(("minutes", minutes.to_string()));
+            words.truncate(words.len() - 3);
+        }
+    }
+
+    let rest = words.join(" ");
+    let (rest, date) = match get_date_from_name(&rest) {
+        Some(date) => ("", Some(date)),
+        None => split_date(&rest),
+    };
+    params.extend(date_params(date));
+
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let at = words.iter().position(|word| *word == "at");
+    let building = words[..at.unwrap_or_else(|| words.len())].join(" ");
+    if !building.is_empty() {
+        params.push(("building", building));
+    }
+    if let Some(at) = at {
+        let time = words[at + 1..].join(" ");
+        if !time.is_empty() && time != "now" {
+            params.push(("at", time));
+        }
+    }
+
+    params
+}
+
+// Query parameters for the server's recommend route, from input like "+vegan at 6pm" along with
+//  the foods that were registered for
+pub fn recommend_params(input: &str, favorites: &[String]) -> Vec<(&'static str, String)> {
+    let query = FoodQuery::parse(input);
+
+    let mut params = open_params(&query.food);
+    params.extend(date_params(query.date));
+    params.extend(query.diet_params());
+
+    let favorites: Vec<String> = favorites
+        .iter()
+        .map(|favorite| FoodQuery::parse(favorite).food)
+        .filter(|favorite| !favorite.is_empty())
+        .collect();
+    if !favorites.is_empty() {
+        params.push(("favorites", favorites.join(",")));
+    }
+
+    params
+}
+
+// Query parameters for a macro target like "berk dinner 600 calories" or "worcester lunch tomorrow
+//  30g protein", for suggesting dishes that fit it
+pub fn target_params(input: &str) -> Option<Vec<(&'static str, String)>> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    if words.len() < 4 {
+        return None;
+    }
+
+    let target = match words[words.len() - 1].to_lowercase().as_str() {
+        "calories" | "calorie" | "cal" | "kcal" => "calories",
+        "protein" => "protein",
+        _ => return None,
+    };
+    let amount = words[words.len() - 2].trim_end_matches('g');
+    amount.parse::<f64>().ok()?;
+
+    let place = words[..words.len() - 2].join(" ");
+    let (place, date) = split_date(&place);
+    let mut place = place.splitn(2, ' ');
+
+    let mut params = vec![
+        ("dining_common", place.next()?.to_string()),
+        ("meal", place.next()?.to_string()),
+        (target, amount.to_string()),
+    ];
+    params.extend(date_params(date));
+    Some(params)
+}
+
+// Splits a rating like "stir fry @berk 4 too salty" into the dish, the number of stars and the
+//  review, if there is one. The stars are the first number from 1 to 5 after the dish's name
+pub fn split_rating(input: &str) -> Option<(String, u8, Option<String>)> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let index = words.iter().skip(1).position(|word| {
+        word.parse::<u8>()
+            .map_or(false, |stars| stars >= 1 && stars <= 5)
+    })? + 1;
+
+    let review = words[index + 1..].join(" ");
+    Some((
+        words[..index].join(" "),
+        words[index].parse().ok()?,
+        if review.is_empty() {
+            None
+        } else {
+            Some(review)
+        },
+    ))
+}
@@ -20,6 +20,11 @@ use serenity::model::gateway::Ready;
 use serenity::model::id::UserId;
 use umass_bot_common::datetime::get_time_till_scheduled;
 use umass_bot_common::error::*;
+use umass_bot_common::food::{Digest, FoodSearch, MenuChanges, Nutrition};
+use umass_bot_common::query::{
+    date_params, free_room_params, open_params, recommend_params, room_schedule_params,
+    room_status_params, split_date, split_rating, target_params, FoodQuery,
+};
 
 // For discord
 use serenity::client::Client;
@@ -51,6 +56,10 @@ use std::io::Write;
 // For multithreading
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
+
+// How often to ask the server whether menus changed since the last check
+static MINUTES_BETWEEN_CHANGE_CHECKS: u64 = 15;
 
 struct Listeners {}
 
@@ -69,13 +78,102 @@ fn send_message(channel_id: ChannelId, message: &str, http: &Arc<Http>) {
     check_msg(channel_id.say(http, message));
 }
 
+// None when the server doesn't know the dining hall, meal or date that was searched for
+fn search_food(food: &str) -> Result<Option<FoodSearch>> {
+    let client = reqwest::Client::new();
+    let mut res = client
+        .get("http://localhost:8000/food/json")
+        .query(&FoodQuery::parse(food).to_params())
+        .send()?;
+
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    Ok(Some(res.error_for_status()?.json()?))
+}
+
+// Bolds the dining common and meal, and lists the diet tags of each dish
+fn render_food_search(search: &FoodSearch) -> String {
+    let mut response = if search.found.is_empty() {
+        format!("{} not found", search.description())
+    } else {
+        let places: Vec<String> = search
+            .found
+            .iter()
+            .map(|place| {
+                let dishes: Vec<String> = place
+                    .dishes
+                    .iter()
+                    .map(|dish| match dish.diets.len() {
+                        0 => dish.label(),
+                        _ => format!("{} _({})_", dish.label(), dish.diets.join(", ")),
+                    })
+                    .collect();
+                format!(
+                    "**{} {}**: {}",
+                    place.dining_common,
+                    place.meal,
+                    dishes.join(", ")
+                )
+            })
+            .collect();
+
+        format!("{}: \n{}", search.description(), places.join("\n"))
+    };
+
+    if let Some(note) = search.unavailable_note() {
+        response = format!("{}\n{}", response, note);
+    }
+
+    response
+}
+
+// Bolds the dining common and meal of each place in the digest
+fn render_digest(digest: &Digest) -> String {
+    let mut lines = vec![digest.heading().to_string()];
+    lines.extend(digest.places.iter().map(|place| {
+        format!(
+            "**{} {}**: {}",
+            place.dining_common,
+            place.meal,
+            place.foods_description()
+        )
+    }));
+    lines.extend(digest.notes());
+    lines.join("\n")
+}
+
 fn check_food(food: &str) -> Result<String> {
+    Ok(match search_food(food)? {
+        Some(search) => render_food_search(&search),
+        None => format!("Couldn't find that dining hall, meal or date for {}", food),
+    })
+}
+
+// Asks the server about past menus, where route is "last", "frequency" or "weekdays"
+//  The input can be narrowed to one dining common, like "sushi @hamp"
+fn check_history(route: &str, input: &str) -> Result<String> {
+    let query = FoodQuery::parse(input);
+    let mut params = vec![("food", query.food.clone())];
+    params.extend(
+        query
+            .place_params()
+            .into_iter()
+            .filter(|(name, _)| *name == "dining_common"),
+    );
+
     let client = reqwest::Client::new();
-    Ok(client
-        .get("http://localhost:8000/food/")
-        .query(&[("food", food)])
-        .send()?
-        .text()?)
+    let mut res = client
+        .get(&format!("http://localhost:8000/history/{}", route))
+        .query(&params)
+        .send()?;
+
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok("Couldn't find that dining hall".to_string());
+    }
+
+    Ok(res.error_for_status()?.text()?)
 }
 
 // Get the discord token file from memory
@@ -111,7 +209,7 @@ impl EventHandler for Handler {
 group!({
     name: "general",
     options: {},
-    commands: [menu, echo, register, deregister, room, run],
+    commands: [menu, listmenu, nutrition, lastserved, howoften, whichday, open, hours, rate, reviews, recommend, plate, echo, register, deregister, room, freerooms, buildings, class, run],
 });
 
 group!({
@@ -130,6 +228,278 @@ fn menu(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
     Ok(())
 }
 
+#[command]
+fn listmenu(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let (input, date) = split_date(args.rest());
+    let mut words = input.splitn(2, ' ');
+    let dining_common: &str = words.next().unwrap_or("");
+    let meal: &str = words.next().unwrap_or("");
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .get(&format!(
+            "http://localhost:8000/menu/{}/{}",
+            dining_common, meal
+        ))
+        .query(&date_params(date))
+        .send()?;
+
+    let response = if res.status().is_success() {
+        res.text()?
+    } else {
+        format!("Couldn't find the {} menu for {}", meal, dining_common)
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn nutrition(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let (dish, date) = split_date(args.rest());
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .get("http://localhost:8000/nutrition/")
+        .query(&[("dish", dish)])
+        .query(&date_params(date))
+        .send()?;
+
+    let response = if res.status().is_success() {
+        res.json::<Nutrition>()?.to_string()
+    } else {
+        format!("Couldn't find {} on the menus", dish)
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn lastserved(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    msg.reply(ctx, &check_history("last", args.rest())?)?;
+    Ok(())
+}
+
+#[command]
+fn howoften(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    msg.reply(ctx, &check_history("frequency", args.rest())?)?;
+    Ok(())
+}
+
+#[command]
+fn whichday(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    msg.reply(ctx, &check_history("weekdays", args.rest())?)?;
+    Ok(())
+}
+
+#[command]
+fn open(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let input: &str = args.rest();
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .get("http://localhost:8000/open/")
+        .query(&open_params(input))
+        .send()?;
+
+    let response = if res.status().is_success() {
+        res.text()?
+    } else {
+        format!("Couldn't understand the time {}", input)
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn hours(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let (input, date) = split_date(args.rest());
+    let mut words = input.splitn(2, ' ');
+    let dining_common: &str = words.next().unwrap_or("");
+    let meal: &str = words.next().unwrap_or("");
+
+    let mut params = date_params(date);
+    if !meal.is_empty() {
+        params.push(("meal", meal.to_string()));
+    }
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .get(&format!("http://localhost:8000/open/{}", dining_common))
+        .query(&params)
+        .send()?;
+
+    let response = if res.status().is_success() {
+        res.text()?
+    } else {
+        format!("Couldn't find the hours for {} {}", dining_common, meal)
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn rate(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let (dish, stars, review) = match split_rating(args.rest()) {
+        Some(rating) => rating,
+        None => {
+            msg.reply(
+                ctx,
+                "Rate a dish from 1 to 5 like `!rate stir fry 4`, optionally followed by a review",
+            )?;
+            return Ok(());
+        }
+    };
+
+    let query = FoodQuery::parse(&dish);
+    let mut params = vec![
+        ("dish", query.food.clone()),
+        ("rating", stars.to_string()),
+        ("user", format!("discord:{}", msg.author.id)),
+    ];
+    params.extend(query.place_params());
+    if let Some(review) = review {
+        params.push(("review", review));
+    }
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .post("http://localhost:8000/ratings/")
+        .query(&params)
+        .send()?;
+
+    let response = if res.status().is_success() {
+        res.text()?
+    } else {
+        format!("Couldn't find {} on today's menus", query.food)
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn reviews(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get("http://localhost:8000/ratings/")
+        .query(&[("dish", args.rest())])
+        .send()?
+        .text()?;
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn recommend(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let input: &str = args.rest();
+
+    // The channel's registered foods are taken as its favorites
+    let favorites: Vec<String> = ctx
+        .data
+        .read()
+        .get::<Listeners>()
+        .unwrap()
+        .iter()
+        .filter(|(channel, _)| *channel == msg.channel_id)
+        .map(|(_, food)| food.clone())
+        .collect();
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .get("http://localhost:8000/recommend/")
+        .query(&recommend_params(input, &favorites))
+        .send()?;
+
+    let response = if res.status().is_success() {
+        res.text()?
+    } else {
+        format!("Couldn't understand the time {}", input)
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn plate(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let user = format!("discord:{}", msg.author.id);
+    let mut words = args.rest().splitn(2, ' ');
+    let action: &str = words.next().unwrap_or("");
+    let input: &str = words.next().unwrap_or("").trim();
+
+    let client = reqwest::Client::new();
+
+    let response = match action {
+        "add" => {
+            let query = FoodQuery::parse(input);
+            let mut params = vec![("user", user), ("dish", query.food.clone())];
+            params.extend(query.place_params());
+
+            let mut res = client
+                .post("http://localhost:8000/plate/")
+                .query(&params)
+                .send()?;
+            if res.status().is_success() {
+                res.text()?
+            } else {
+                format!("Couldn't find {} on today's menus", query.food)
+            }
+        }
+        "remove" | "clear" => {
+            let mut params = vec![("user", user)];
+            if action == "remove" {
+                params.push(("dish", input.to_string()));
+            }
+
+            let mut res = client
+                .delete("http://localhost:8000/plate/")
+                .query(&params)
+                .send()?;
+            if res.status().is_success() {
+                res.text()?
+            } else {
+                format!("Couldn't find {} on your plate", input)
+            }
+        }
+        "suggest" => match target_params(input) {
+            Some(mut params) => {
+                params.push(("user", user));
+
+                let mut res = client
+                    .get("http://localhost:8000/plate/suggest")
+                    .query(&params)
+                    .send()?;
+                if res.status().is_success() {
+                    res.text()?
+                } else {
+                    format!("Couldn't find the menu for {}", input)
+                }
+            }
+            None => "Suggest dishes for a target like `!plate suggest berk dinner 600 calories` or `!plate suggest worcester lunch 30g protein`".to_string(),
+        },
+        _ => client
+            .get("http://localhost:8000/plate/")
+            .query(&[("user", user)])
+            .send()?
+            .text()?,
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
 #[command]
 fn echo(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
     let input: &str = args.rest();
@@ -194,18 +564,96 @@ fn room(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
 
     let client = reqwest::Client::new();
 
-    let res = client
-        .get("http://localhost:8000/room/")
-        .query(&[("room", room)])
+    // Like "LGRT 141 free?" asks whether it's free, rather than for its schedule
+    let mut res = match room_status_params(room) {
+        Some(params) => client
+            .get("http://localhost:8000/room/status")
+            .query(&params)
+            .send()?,
+        None => client
+            .get("http://localhost:8000/room/schedule")
+            .query(&room_schedule_params(room))
+            .send()?,
+    };
+
+    let response = if res.status().is_success() {
+        res.text()?
+    } else {
+        "No classes meet in that room".to_string()
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn freerooms(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let input: &str = args.rest();
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .get("http://localhost:8000/room/free")
+        .query(&free_room_params(input))
         .send()?;
 
     let response = if res.status().is_success() {
-        "Some classes meet in that room"
+        res.text()?
     } else {
-        "No classes meet in that room"
+        format!(
+            "Couldn't find the building or understand the time in {}",
+            input
+        )
     };
 
-    msg.reply(ctx, response)?;
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn buildings(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let building: &str = args.rest().trim();
+
+    let mut params = vec![];
+    if !building.is_empty() {
+        params.push(("building", building));
+    }
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .get("http://localhost:8000/room/buildings")
+        .query(&params)
+        .send()?;
+
+    let response = if res.status().is_success() {
+        res.text()?
+    } else {
+        format!("Couldn't find the building {}", building)
+    };
+
+    msg.reply(ctx, &response)?;
+    Ok(())
+}
+
+#[command]
+fn class(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let search: &str = args.rest();
+
+    let client = reqwest::Client::new();
+
+    let mut res = client
+        .get("http://localhost:8000/class/")
+        .query(&[("search", search)])
+        .send()?;
+
+    let response = if res.status().is_success() {
+        res.text()?
+    } else {
+        format!("Couldn't find any classes matching {}", search)
+    };
+
+    msg.reply(ctx, &response)?;
     Ok(())
 }
 
@@ -275,14 +723,83 @@ fn save_listeners(pairs: &[(ChannelId, String)]) -> Result<()> {
     Ok(())
 }
 
-fn check_for_foods(listeners: Vec<(ChannelId, String)>, http: &Arc<Http>) {
+fn get_changes(query: &FoodQuery, since: Option<u64>) -> Result<MenuChanges> {
+    let mut params = vec![("food", query.food.clone())];
+    params.extend(query.place_params());
+    if let Some(since) = since {
+        params.push(("since", since.to_string()));
+    }
+
+    let client = reqwest::Client::new();
+    Ok(client
+        .get("http://localhost:8000/food/changes")
+        .query(&params)
+        .send()?
+        .error_for_status()?
+        .json()?)
+}
+
+// Tells listeners about menu changes since the last check, and returns the id to check from next
+//  The first check only finds out where the server's changes are up to
+fn check_for_changes(
+    listeners: Vec<(ChannelId, String)>,
+    since: Option<u64>,
+    http: &Arc<Http>,
+) -> Option<u64> {
+    let latest = match get_changes(&FoodQuery::parse(""), None) {
+        Ok(changes) => changes.latest,
+        Err(_) => return since,
+    };
+    let since = match since {
+        // The server restarted, so it's numbering changes from the start again
+        Some(since) if since > latest => 0,
+        Some(since) => since,
+        None => return Some(latest),
+    };
+    if since == latest {
+        return Some(latest);
+    }
+
     listeners.into_iter().for_each(|(channel, food)| {
-        println!("Checking on {:?} for {}", channel, food);
-        match check_food(&food) {
-            Ok(response) => send_message(channel, &response, http),
-            Err(_) => send_message(channel, &format!("Couldn't check for {}", food), http),
+        match get_changes(&FoodQuery::parse(&food), Some(since)) {
+            Ok(changes) => changes
+                .changes
+                .iter()
+                // Anything newer will be picked up by the next check
+                .filter(|change| change.id < latest)
+                .for_each(|change| send_message(channel, &change.describe(), http)),
+            Err(_) => println!("Couldn't check for changes to {}", food),
         }
     });
+
+    Some(latest)
+}
+
+// Sends each channel one message with all of the foods it registered for
+fn check_for_foods(listeners: Vec<(ChannelId, String)>, http: &Arc<Http>) {
+    let mut digests: Vec<(ChannelId, Digest)> = vec![];
+    for (channel, food) in listeners {
+        println!("Checking on {:?} for {}", channel, food);
+        let index = match digests
+            .iter()
+            .position(|(digest_channel, _)| *digest_channel == channel)
+        {
+            Some(index) => index,
+            None => {
+                digests.push((channel, Digest::default()));
+                digests.len() - 1
+            }
+        };
+
+        match search_food(&food) {
+            Ok(Some(search)) => digests[index].1.add(&search),
+            _ => digests[index].1.add_failed(&food),
+        }
+    }
+
+    for (channel, digest) in digests {
+        send_message(channel, &render_digest(&digest), http);
+    }
 }
 
 fn main() {
@@ -319,6 +836,19 @@ fn main() {
         }
     });
 
+    // Menu changes loop
+    let data_clone = Arc::clone(&client.data);
+    let http = Arc::clone(&client.cache_and_http.http);
+    thread::spawn(move || {
+        let data = data_clone;
+        let mut last_checked = None;
+        loop {
+            let listeners = data.read().get::<Listeners>().unwrap().to_vec();
+            last_checked = check_for_changes(listeners, last_checked, &http);
+            thread::sleep(Duration::from_secs(60 * MINUTES_BETWEEN_CHANGE_CHECKS));
+        }
+    });
+
     client.with_framework(
         StandardFramework::new()
             .configure(|c| c.prefix("!").owners(owners))
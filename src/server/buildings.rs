@@ -0,0 +1,192 @@
+// Buildings and the rooms in them, so rooms can be found by names like "LGRT 141" or "ilc s131"
+//
+// SPIRE spells buildings several different ways, so buildings.json lists each building's full
+// name along with the abbreviations people use and the ways SPIRE writes it. Buildings that
+// aren't listed there are named however SPIRE spells them.
+
+use crate::rooms::RoomStore;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+
+use umass_bot_common::query::normalize;
+
+// Words in SPIRE's room names that come between the building and the room number
+static ROOM_WORDS: [&str; 4] = ["room", "rm", "rom", "-"];
+
+// Wings that are sometimes written apart from the room number, like "Furcolo W 43"
+static WINGS: [&str; 4] = ["n", "s", "e", "w"];
+
+#[derive(Clone, Debug, Deserialize)]
+struct BuildingNames {
+    name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    // How SPIRE writes the building, besides its full name and aliases
+    #[serde(default)]
+    spellings: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Building {
+    pub name: String,
+    pub aliases: Vec<String>,
+    // Each room's number, like "S131", along with its name in spire.json
+    pub rooms: BTreeMap<String, String>,
+    // The name, aliases and spellings, normalized
+    #[serde(skip)]
+    names: Vec<String>,
+}
+
+pub struct BuildingIndex {
+    buildings: Vec<Building>,
+    // Every room's name in spire.json, keyed by the normalized name
+    room_names: HashMap<String, String>,
+}
+
+// Splits a room like "Integ. Learning Center S131", "Herter Hall room 601" or "Machmer E - 23" into
+//  its building and room number. Rooms without a number, like "TBA", are just a building
+fn split_room(room: &str) -> (String, Option<String>) {
+    let mut words: Vec<&str> = room.split_whitespace().collect();
+    let mut number = match words.last() {
+        Some(last) if last.chars().any(|c| c.is_ascii_digit()) => words.pop().map(String::from),
+        _ => None,
+    };
+
+    while words.last().map_or(false, |word| {
+        ROOM_WORDS.contains(&word.to_lowercase().as_str())
+    }) {
+        words.pop();
+    }
+    if number.is_some() && words.len() > 1 {
+        if let Some(wing) = words.last() {
+            if WINGS.contains(&wing.to_lowercase().as_str()) {
+                number = number.map(|number| format!("{}{}", wing.to_uppercase(), number));
+                words.pop();
+            }
+        }
+    }
+
+    (words.join(" "), number)
+}
+
+fn load_building_names() -> Vec<BuildingNames> {
+    let mut buildings_json = String::new();
+    let _ = File::open("buildings.json")
+        .expect("No buildings json file")
+        .read_to_string(&mut buildings_json);
+
+    serde_json::from_str(buildings_json.trim()).expect("Invalid buildings json file")
+}
+
+pub fn index_buildings(rooms: &RoomStore) -> BuildingIndex {
+    let mut buildings: Vec<Building> = load_building_names()
+        .into_iter()
+        .map(|building| Building {
+            names: std::iter::once(&building.name)
+                .chain(building.aliases.iter())
+                .chain(building.spellings.iter())
+                .map(|name| normalize(name))
+                .collect(),
+            name: building.name,
+            aliases: building.aliases,
+            rooms: BTreeMap::new(),
+        })
+        .collect();
+
+    for room in rooms.keys() {
+        let (spelling, number) = split_room(room);
+        let number = match number {
+            Some(number) => number,
+            None => continue,
+        };
+
+        let spelling_normalized = normalize(&spelling);
+        let index = match buildings
+            .iter()
+            .position(|building| building.names.contains(&spelling_normalized))
+        {
+            Some(index) => index,
+            None => {
+                buildings.push(Building {
+                    name: spelling,
+                    aliases: vec![],
+                    rooms: BTreeMap::new(),
+                    names: vec![spelling_normalized],
+                });
+                buildings.len() - 1
+            }
+        };
+        buildings[index].rooms.insert(number, room.clone());
+    }
+
+    buildings.retain(|building| !building.rooms.is_empty());
+    buildings.sort_by(|a, b| a.name.cmp(&b.name));
+
+    BuildingIndex {
+        buildings,
+        room_names: rooms
+            .keys()
+            .map(|room| (normalize(room), room.clone()))
+            .collect(),
+    }
+}
+
+impl Building {
+    pub fn contains(&self, room: &str) -> bool {
+        self.rooms.values().any(|name| name == room)
+    }
+
+    fn title(&self) -> String {
+        if self.aliases.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} ({})", self.name, self.aliases.join(", "))
+        }
+    }
+
+    pub fn describe_rooms(&self) -> String {
+        let numbers: Vec<&str> = self.rooms.keys().map(|number| number.as_str()).collect();
+        format!("{}: \n{}", self.title(), numbers.join(", "))
+    }
+}
+
+impl BuildingIndex {
+    pub fn buildings(&self) -> &[Building] {
+        &self.buildings
+    }
+
+    pub fn find_building(&self, name: &str) -> Option<&Building> {
+        let name = normalize(name);
+        self.buildings
+            .iter()
+            .find(|building| building.names.contains(&name))
+    }
+
+    // The room's name in spire.json, from either that name or a building and room number
+    pub fn find_room(&self, name: &str) -> Option<&str> {
+        if let Some(room) = self.room_names.get(&normalize(name)) {
+            return Some(room);
+        }
+
+        let (building, number) = split_room(name);
+        let number = normalize(&number?);
+        self.find_building(&building)?
+            .rooms
+            .iter()
+            .find(|(room_number, _)| normalize(room_number) == number)
+            .map(|(_, room)| room.as_str())
+    }
+
+    pub fn describe_buildings(&self) -> String {
+        self.buildings
+            .iter()
+            .map(|building| format!("{}: {} rooms", building.title(), building.rooms.len()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
@@ -1,87 +1,424 @@
 use crate::events::get_document;
+use crate::history::{archive_menus, load_archived_menus, load_history, HistoryStore};
+use crate::matching::FoodMatcher;
 use chrono::Date;
-use std::ops::DerefMut;
+use chrono::Datelike;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
 use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde::Deserialize;
+use serde::Serialize;
 
 use select::document::Document;
+use select::node::{Data, Node};
 use select::predicate::Attr;
 use select::predicate::Class;
 use select::predicate::Predicate;
 
+use umass_bot_common::datetime::format_date;
 use umass_bot_common::error::*;
+use umass_bot_common::food::{
+    parse_amount, DishMatch, FoodSearch, MenuChange, MenuChanges, MenuMatch, Nutrition,
+};
+use umass_bot_common::query::normalize;
 
 use std::fmt;
 
 use chrono::offset::FixedOffset;
 
 use umass_bot_common::datetime::get_datetime;
-use umass_bot_common::datetime::get_day_of_week;
 
 use chrono::Weekday::*;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Meal {
     Breakfast,
     Lunch,
+    // Served instead of lunch, usually on the weekends
+    Brunch,
     Dinner,
     LateNight,
     GrabAndGo,
 }
 
-type InternalFoodStore = (Date<FixedOffset>, DiningCommonsDocs);
+// Menus are cached for each date, and kept up to date by the refresher in refresh.rs
+#[derive(Default)]
+pub struct InternalFoodStore {
+    menus: HashMap<Date<FixedOffset>, DiningCommonsMenus>,
+    // Changes the refresher noticed in menus that had already been fetched, oldest first
+    changes: Vec<MenuChange>,
+    next_change_id: u64,
+    dining_commons: Vec<DiningCommon>,
+    // The dishes on every archived day's menus
+    history: HistoryStore,
+}
+
 pub type FoodStore = Arc<Mutex<InternalFoodStore>>;
 
+// Changes are saved so that a restart doesn't lose any the clients haven't told listeners about
+static CHANGES_FILE: &str = "menu_changes.json";
+
+static TOO_FAR_AHEAD: &str = "Menus aren't posted that far ahead yet";
+
+// How many days before today to keep cached menus for
+static DAYS_TO_KEEP: i64 = 7;
+
+// How many days after today menus can be asked for, since the dining site doesn't have them any
+//  further ahead than that
+static MAX_DAYS_AHEAD: i64 = 14;
+
 use self::Meal::*;
 
-pub fn get_store() -> Result<FoodStore> {
-    Ok(Arc::new(Mutex::new((
-        get_datetime().date(),
-        get_menus_no_cache()?,
-    ))))
+pub fn get_store() -> FoodStore {
+    let today = get_datetime().date();
+    let dining_commons = load_dining_commons();
+
+    let history = load_history();
+
+    let menus = get_menus_no_cache(&dining_commons, today);
+    archive(today, &menus, &history);
+
+    let mut store = InternalFoodStore::default();
+    store.menus.insert(today, menus);
+    store.dining_commons = dining_commons;
+    store.history = history;
+    if let Some(saved) = load_changes() {
+        store.changes = saved.changes;
+        store.next_change_id = saved.latest;
+    }
+
+    Arc::new(Mutex::new(store))
 }
 
 impl fmt::Display for Meal {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
-        formatter.write_str(match get_day_of_week() {
-            Mon | Tue | Wed | Thu | Fri => match self {
-                Breakfast => "Breakfast",
-                Lunch => "Lunch",
-                Dinner => "Dinner",
-                LateNight => "Late Night",
-                GrabAndGo => "Grab n' Go",
-            },
-            Sat | Sun => match self {
-                Breakfast => "Breakfast",
-                Lunch => "Brunch",
-                Dinner => "Dinner",
-                LateNight => "Late Night",
-                GrabAndGo => "Grab n' Go",
-            },
+        formatter.write_str(match self {
+            Breakfast => "Breakfast",
+            Lunch => "Lunch",
+            Brunch => "Brunch",
+            Dinner => "Dinner",
+            LateNight => "Late Night",
+            GrabAndGo => "Grab n' Go",
         })
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum DiningCommon {
-    Berk,
-    Hamp,
-    Frank,
-    Worcester,
+impl Meal {
+    // Brunch takes lunch's place, so asking for either one finds whichever is being served
+    pub fn matches(self, other: Meal) -> bool {
+        match (self, other) {
+            (Lunch, Brunch) | (Brunch, Lunch) => true,
+            _ => self == other,
+        }
+    }
+}
+
+// Any place with a menu on the dining site, from the dining commons to the retail locations
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct DiningCommon {
+    // Shown to users, and what its menus are saved under
+    pub name: String,
+    // Its part of the dining site's URLs, like "berkshire" or "blue-wall"
+    pub code: String,
+}
+
+impl fmt::Display for DiningCommon {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        formatter.write_str(&self.name)
+    }
+}
+
+// Get the list of locations from memory, so new ones can be added without changing the code
+fn load_dining_commons() -> Vec<DiningCommon> {
+    let mut locations_json = String::new();
+    let _ = File::open("locations.json")
+        .expect("No locations json file")
+        .read_to_string(&mut locations_json);
+
+    serde_json::from_str(locations_json.trim()).expect("Invalid locations json file")
+}
+
+// No file just means no changes have been saved yet, but one that can't be read stops the
+//  server, since the next change would overwrite it
+fn load_changes() -> Option<MenuChanges> {
+    match fs::read_to_string(CHANGES_FILE) {
+        Ok(changes) => {
+            Some(serde_json::from_str(&changes).expect("Invalid menu changes json file"))
+        }
+        Err(ref err) if err.kind() == ErrorKind::NotFound => None,
+        Err(err) => panic!("Couldn't read the menu changes file: {}", err),
+    }
+}
+
+fn write_changes(store: &InternalFoodStore) -> Result<()> {
+    let saved = MenuChanges {
+        latest: store.next_change_id,
+        changes: store.changes.clone(),
+    };
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(CHANGES_FILE)?
+        .write_all(serde_json::to_string(&saved)?.as_bytes())?;
+
+    Ok(())
+}
+
+pub fn dining_commons(store: &FoodStore) -> Vec<DiningCommon> {
+    store.lock().unwrap().dining_commons.clone()
+}
+
+pub fn history(store: &FoodStore) -> HistoryStore {
+    store.lock().unwrap().history.clone()
 }
 
-pub struct DiningCommonsDocs {
-    berk: String,
-    hamp: String,
-    frank: String,
-    worcester: String,
+// A single item on a menu, along with the data attributes from its nutrition lightbox
+// (with the "data-" prefix removed, so "data-calories" is stored as "calories")
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Dish {
+    pub name: String,
+    pub attributes: HashMap<String, String>,
 }
 
-use self::DiningCommon::*;
+impl Dish {
+    // Splits a comma separated lightbox attribute, like "allergens" or "clean-diet-str"
+    fn attribute_list(&self, attribute: &str) -> Vec<String> {
+        match self.attributes.get(attribute) {
+            Some(list) => list
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn allergens(&self) -> Vec<String> {
+        self.attribute_list("allergens")
+    }
+
+    // Diet tags like "Vegan", "Vegetarian" or "Halal"
+    pub fn diets(&self) -> Vec<String> {
+        self.attribute_list("clean-diet-str")
+    }
+
+    fn attribute(&self, attribute: &str) -> Option<String> {
+        self.attributes
+            .get(attribute)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn amount(&self, attribute: &str) -> Option<f64> {
+        self.attributes
+            .get(attribute)
+            .and_then(|value| parse_amount(value))
+    }
+
+    pub fn to_match(&self) -> DishMatch {
+        DishMatch {
+            name: self.name.clone(),
+            diets: self.diets(),
+            allergens: self.allergens(),
+            average_rating: None,
+            ratings: 0,
+        }
+    }
+
+    pub fn nutrition(&self) -> Nutrition {
+        Nutrition {
+            dish: self.name.clone(),
+            serving_size: self.attribute("serving-size"),
+            calories: self.amount("calories"),
+            total_fat: self.amount("total-fat"),
+            saturated_fat: self.amount("sat-fat"),
+            cholesterol: self.amount("cholesterol"),
+            sodium: self.amount("sodium"),
+            total_carbs: self.amount("total-carb"),
+            fiber: self.amount("dietary-fiber"),
+            sugars: self.amount("sugars"),
+            protein: self.amount("protein"),
+            ingredients: self.attribute("ingredient-list"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DietFilter {
+    // Diet tags that a dish must have
+    pub diets: Vec<String>,
+    // Allergens that a dish must not contain
+    pub excluded_allergens: Vec<String>,
+}
+
+impl DietFilter {
+    // Both lists are comma separated, as sent by the clients
+    pub fn from_params(diets: Option<String>, excluded_allergens: Option<String>) -> DietFilter {
+        let split = |list: Option<String>| -> Vec<String> {
+            list.map(|list| {
+                list.split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+        };
+
+        DietFilter {
+            diets: split(diets),
+            excluded_allergens: split(excluded_allergens),
+        }
+    }
+
+    fn has_diet(dish_diets: &[String], diet: &str) -> bool {
+        dish_diets.iter().any(|dish_diet| {
+            dish_diet == diet
+                // Anything vegan is also vegetarian, but the site only lists the stricter tag
+                || (diet == "vegetarian" && dish_diet == "vegan")
+        })
+    }
+
+    pub fn allows(&self, dish: &Dish) -> bool {
+        let dish_diets: Vec<String> = dish.diets().iter().map(|d| normalize(d)).collect();
+        let dish_allergens: Vec<String> = dish.allergens().iter().map(|a| normalize(a)).collect();
+
+        let has_diets = self
+            .diets
+            .iter()
+            .all(|diet| DietFilter::has_diet(&dish_diets, &normalize(diet)));
+
+        // Substring matching so that excluding "nuts" also excludes "Tree Nuts" and "Peanuts"
+        let avoids_allergens = self.excluded_allergens.iter().all(|allergen| {
+            let allergen = normalize(allergen);
+            !dish_allergens
+                .iter()
+                .any(|dish_allergen| dish_allergen.contains(&allergen))
+        });
+
+        has_diets && avoids_allergens
+    }
+}
+
+// Narrows a search to one dining common and/or meal
+#[derive(Clone, Debug, Default)]
+pub struct PlaceFilter {
+    pub dining_common: Option<DiningCommon>,
+    pub meal: Option<Meal>,
+}
+
+impl PlaceFilter {
+    // None if either was given but couldn't be found
+    pub fn from_params(
+        dining_common: Option<String>,
+        meal: Option<String>,
+        dining_commons: &[DiningCommon],
+    ) -> Option<PlaceFilter> {
+        match dining_common {
+            Some(name) => {
+                let (dining_common, meal) = find_place(&name, meal.as_ref(), dining_commons)?;
+                Some(PlaceFilter {
+                    dining_common: Some(dining_common),
+                    meal,
+                })
+            }
+            None => Some(PlaceFilter {
+                dining_common: None,
+                meal: match meal {
+                    Some(name) => Some(meal_from_name(&name)?),
+                    None => None,
+                },
+            }),
+        }
+    }
+
+    fn allows_dining_common(&self, dining_common: &DiningCommon) -> bool {
+        self.dining_common
+            .as_ref()
+            .map_or(true, |allowed| allowed == dining_common)
+    }
+
+    fn allows_meal(&self, meal: Meal) -> bool {
+        self.meal.map_or(true, |allowed| allowed.matches(meal))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Station {
+    pub name: String,
+    pub dishes: Vec<Dish>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MealMenu {
+    pub meal: Meal,
+    pub stations: Vec<Station>,
+}
+
+impl MealMenu {
+    pub fn dishes(&self) -> impl Iterator<Item = &Dish> {
+        self.stations
+            .iter()
+            .flat_map(|station| station.dishes.iter())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DiningCommonMenu {
+    // The dining common's name
+    pub dining_common: String,
+    pub meals: Vec<MealMenu>,
+}
+
+impl DiningCommonMenu {
+    pub fn meal(&self, meal: Meal) -> Option<&MealMenu> {
+        self.meals
+            .iter()
+            .find(|meal_menu| meal_menu.meal.matches(meal))
+    }
+}
+
+// The menus for every dining common on one day
+//  A dining common is missing if its menu couldn't be fetched
+//  Keyed by the dining common's name
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DiningCommonsMenus {
+    menus: HashMap<String, DiningCommonMenu>,
+}
+
+impl DiningCommonsMenus {
+    pub fn get(&self, dining_common: &DiningCommon) -> Option<&DiningCommonMenu> {
+        self.menus.get(&dining_common.name)
+    }
+
+    // In order of name
+    pub fn all(&self) -> Vec<&DiningCommonMenu> {
+        let mut menus: Vec<&DiningCommonMenu> = self.menus.values().collect();
+        menus.sort_by(|a, b| a.dining_common.cmp(&b.dining_common));
+        menus
+    }
+
+    pub fn unavailable(&self, dining_commons: &[DiningCommon]) -> Vec<DiningCommon> {
+        dining_commons
+            .iter()
+            .filter(|dining_common| !self.menus.contains_key(&dining_common.name))
+            .cloned()
+            .collect()
+    }
+}
 
 fn get_meal_code(meal: Meal) -> String {
     match meal {
         Breakfast => "breakfast_menu",
-        Lunch => "lunch_menu",
+        Lunch | Brunch => "lunch_menu",
         Dinner => "dinner_menu",
         LateNight => "latenight_menu",
         GrabAndGo => "grabngo",
@@ -89,22 +426,69 @@ fn get_meal_code(meal: Meal) -> String {
     .to_string()
 }
 
-fn get_dining_common_code(dining_common: DiningCommon) -> String {
-    match dining_common {
-        Worcester => "worcester",
-        Frank => "franklin",
-        Hamp => "hampshire",
-        Berk => "berkshire",
+// Finds the meal with a name like "dinner", "Late Night" or "brunch"
+pub fn meal_from_name(name: &str) -> Option<Meal> {
+    let name = normalize(name);
+    if name.is_empty() {
+        return None;
     }
-    .to_string()
+
+    [Breakfast, Lunch, Brunch, Dinner, LateNight, GrabAndGo]
+        .iter()
+        .find(|meal| {
+            name == normalize(&meal.to_string())
+                || normalize(&get_meal_code(**meal)).starts_with(&name)
+        })
+        .cloned()
+}
+
+// Finds the dining common with a name like "berk", "Hampshire" or "blue wall"
+pub fn dining_common_from_name(
+    name: &str,
+    dining_commons: &[DiningCommon],
+) -> Option<DiningCommon> {
+    let name = normalize(name);
+    if name.is_empty() {
+        return None;
+    }
+
+    dining_commons
+        .iter()
+        .find(|dining_common| {
+            name == normalize(&dining_common.name)
+                || normalize(&dining_common.code).starts_with(&name)
+        })
+        .cloned()
 }
 
-pub fn get_menu_no_cache(dining_common: DiningCommon) -> Result<String> {
-    let dining_common_id = get_dining_common_code(dining_common);
+// Finds the dining common and meal in a place the clients split after its first word, like "blue"
+//  and "wall dinner", by matching the longest run of leading words to a dining common's name
+//  None if no dining common matches, or the words after it aren't a meal
+pub fn find_place(
+    dining_common: &str,
+    meal: Option<&String>,
+    dining_commons: &[DiningCommon],
+) -> Option<(DiningCommon, Option<Meal>)> {
+    let words: Vec<&str> = dining_common
+        .split_whitespace()
+        .chain(meal.into_iter().flat_map(|meal| meal.split_whitespace()))
+        .collect();
 
+    (1..=words.len()).rev().find_map(|length| {
+        let dining_common = dining_common_from_name(&words[..length].join(" "), dining_commons)?;
+        let meal = match &words[length..] {
+            [] => None,
+            meal => Some(meal_from_name(&meal.join(" "))?),
+        };
+        Some((dining_common, meal))
+    })
+}
+
+pub fn get_menu_no_cache(dining_common: &DiningCommon, date: Date<FixedOffset>) -> Result<String> {
     let url: &str = &format!(
-        "http://umassdining.com/locations-menus/{dining_common}/menu",
-        dining_common = dining_common_id
+        "http://umassdining.com/locations-menus/{dining_common}/menu?date={date}",
+        dining_common = dining_common.code,
+        date = date.format("%m/%d/%Y")
     );
 
     println!("{}", url);
@@ -112,108 +496,716 @@ pub fn get_menu_no_cache(dining_common: DiningCommon) -> Result<String> {
     Ok(get_document(url)?)
 }
 
-pub fn get_menus_no_cache() -> Result<DiningCommonsDocs> {
-    Ok(DiningCommonsDocs {
-        berk: get_menu_no_cache(Berk)?,
-        hamp: get_menu_no_cache(Hamp)?,
-        frank: get_menu_no_cache(Frank)?,
-        worcester: get_menu_no_cache(Worcester)?,
-    })
+// Fetches the dining commons' menus all at once, so one slow or broken page doesn't hold up
+//  the rest. A dining common that fails keeps whatever menu it had before.
+fn update_menus(
+    menus: &mut DiningCommonsMenus,
+    dining_commons: &[DiningCommon],
+    date: Date<FixedOffset>,
+) {
+    let handles: Vec<_> = dining_commons
+        .iter()
+        .map(|dining_common| {
+            let dining_common = dining_common.clone();
+            thread::spawn(move || {
+                get_menu_no_cache(&dining_common, date)
+                    .map(|html| parse_menu(&dining_common, &html, date))
+                    .map_err(|err| err.to_string())
+            })
+        })
+        .collect();
+
+    for (dining_common, handle) in dining_commons.iter().zip(handles) {
+        match handle.join() {
+            Ok(Ok(menu)) => {
+                menus.menus.insert(dining_common.name.clone(), menu);
+            }
+            Ok(Err(err)) => println!("Couldn't get the menu for {}: {}", dining_common, err),
+            Err(_) => println!("Getting the menu for {} panicked", dining_common),
+        }
+    }
+}
+
+pub fn get_menus_no_cache(
+    dining_commons: &[DiningCommon],
+    date: Date<FixedOffset>,
+) -> DiningCommonsMenus {
+    let mut menus = DiningCommonsMenus::default();
+    update_menus(&mut menus, dining_commons, date);
+    menus
 }
 
-fn get_menu_document(dining_common: DiningCommon, store: &FoodStore) -> Result<Document> {
-    let mut unlocked_store = store.lock().unwrap();
-    let store: &mut InternalFoodStore = unlocked_store.deref_mut();
-    if store.0 != get_datetime().date() {
-        store.0 = get_datetime().date();
-        store.1 = get_menus_no_cache()?;
+// Future menus can still change, so only the ones that have already been served are archived
+fn archive(date: Date<FixedOffset>, menus: &DiningCommonsMenus, history: &HistoryStore) {
+    if date > get_datetime().date() || menus.all().is_empty() {
+        return;
     }
 
-    Ok(Document::from(&*match dining_common {
-        Berk => store.1.berk.clone(),
-        Hamp => store.1.hamp.clone(),
-        Frank => store.1.frank.clone(),
-        Worcester => store.1.worcester.clone(),
-    }))
+    if let Err(err) = archive_menus(date, menus, history) {
+        println!("Couldn't archive the menus for {}: {}", date, err);
+    }
 }
 
-pub fn get_on_menu(
-    dining_common: DiningCommon,
-    meal: Meal,
-    item: &str,
-    store: &FoodStore,
-) -> Result<Vec<String>> {
-    let menu_document = get_menu_document(dining_common, store)?;
-    let base_nodes: Option<select::node::Node> = menu_document
+// Pulls the "data-*" attributes off of a nutrition lightbox node
+fn get_lightbox_attributes(node: &Node) -> HashMap<String, String> {
+    match node.data() {
+        Data::Element(_, attributes) => attributes
+            .iter()
+            .filter(|(name, _)| name.local.starts_with("data-"))
+            .map(|(name, value)| (name.local[5..].to_string(), value.to_string()))
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+fn parse_dish(node: &Node) -> Dish {
+    let attributes = get_lightbox_attributes(node);
+    let name = match attributes.get("dish-name") {
+        Some(name) => name.trim().to_string(),
+        None => node.text().trim().to_string(),
+    };
+
+    Dish { name, attributes }
+}
+
+fn parse_meal(menu_document: &Document, meal: Meal) -> Option<MealMenu> {
+    let content = menu_document
         .find(Attr("id", &get_meal_code(meal)[..]).descendant(Attr("id", "content_text")))
-        .nth(0);
+        .nth(0)?;
 
-    if base_nodes.is_none() {
-        println!(
-            "Tried to find food at {:?} {:?} but failed to parse page",
-            dining_common, meal
-        );
-        return Ok(vec![]);
+    let mut stations: Vec<Station> = vec![];
+
+    // Station headers and the dishes under them are siblings, so walk through them in order
+    for node in content.descendants() {
+        if Class("menu_category_name").matches(&node) {
+            stations.push(Station {
+                name: node.text().trim().to_string(),
+                dishes: vec![],
+            });
+        } else if Class("lightbox-nutrition").matches(&node) {
+            if stations.is_empty() {
+                stations.push(Station {
+                    name: String::new(),
+                    dishes: vec![],
+                });
+            }
+            stations.last_mut().unwrap().dishes.push(parse_dish(&node));
+        }
     }
 
-    let nodes: Vec<String> = base_nodes
-        .unwrap()
-        .find(Class("lightbox-nutrition"))
-        .map(|node| node.text())
-        .collect();
+    // Breaks and closed days can still have a tab for the meal, just with nothing on it
+    if stations.iter().all(|station| station.dishes.is_empty()) {
+        return None;
+    }
+
+    Some(MealMenu { meal, stations })
+}
+
+// Whether the lunch tab is brunch, going by its label, or by whether it's the weekend if the
+//  label can't be found
+fn is_brunch(menu_document: &Document, date: Date<FixedOffset>) -> bool {
+    let tab_link = format!("#{}", get_meal_code(Lunch));
+    match menu_document.find(Attr("href", &tab_link[..])).nth(0) {
+        Some(tab) => tab.text().to_lowercase().contains("brunch"),
+        None => date.weekday() == Sat || date.weekday() == Sun,
+    }
+}
+
+pub fn parse_menu(
+    dining_common: &DiningCommon,
+    html: &str,
+    date: Date<FixedOffset>,
+) -> DiningCommonMenu {
+    let menu_document = Document::from(html);
+    let lunch = if is_brunch(&menu_document, date) {
+        Brunch
+    } else {
+        Lunch
+    };
+
+    DiningCommonMenu {
+        dining_common: dining_common.name.clone(),
+        meals: [Breakfast, lunch, Dinner, LateNight, GrabAndGo]
+            .iter()
+            .filter_map(|meal| parse_meal(&menu_document, *meal))
+            .collect(),
+    }
+}
+
+fn dish_names(meal_menu: Option<&MealMenu>) -> Vec<String> {
+    match meal_menu {
+        Some(meal_menu) => meal_menu.dishes().map(|dish| dish.name.clone()).collect(),
+        None => vec![],
+    }
+}
+
+// The dishes that were added to and removed from each of a dining common's meals
+fn diff_menus(
+    old: &DiningCommonMenu,
+    new: &DiningCommonMenu,
+) -> Vec<(Meal, Vec<String>, Vec<String>)> {
+    let mut meals: Vec<Meal> = new.meals.iter().map(|meal_menu| meal_menu.meal).collect();
+    for meal_menu in &old.meals {
+        if !meals.iter().any(|meal| meal.matches(meal_menu.meal)) {
+            meals.push(meal_menu.meal);
+        }
+    }
 
-    let filtered: Vec<String> = nodes
+    meals
         .into_iter()
-        .map(|text| text.to_lowercase())
-        .filter(|text| text.contains(item.to_lowercase().as_str()))
-        .collect();
+        .filter_map(|meal| {
+            let old_dishes = dish_names(old.meal(meal));
+            let new_dishes = dish_names(new.meal(meal));
+
+            let added: Vec<String> = new_dishes
+                .iter()
+                .filter(|dish| !old_dishes.contains(dish))
+                .cloned()
+                .collect();
+            let removed: Vec<String> = old_dishes
+                .iter()
+                .filter(|dish| !new_dishes.contains(dish))
+                .cloned()
+                .collect();
 
-    let found = filtered.join(" ");
-    if found != String::new() {
-        println!("{}", found);
-    }
-
-    Ok(filtered)
-}
-
-fn which_meals(dc: DiningCommon) -> Vec<Meal> {
-    match get_day_of_week() {
-        Mon | Tue | Wed | Thu => match dc {
-            Berk => vec![Lunch, Dinner, LateNight, GrabAndGo],
-            Hamp | Frank => vec![Breakfast, Lunch, Dinner, GrabAndGo],
-            Worcester => vec![Breakfast, Lunch, Dinner, LateNight, GrabAndGo],
-        },
-        Fri => match dc {
-            Berk => vec![Lunch, Dinner, LateNight, GrabAndGo],
-            Hamp | Frank | Worcester => vec![Breakfast, Lunch, Dinner, GrabAndGo],
-        },
-        Sat => match dc {
-            Berk => vec![Lunch, Dinner, LateNight],
-            Hamp | Frank | Worcester => vec![Lunch, Dinner],
-        },
-        Sun => match dc {
-            Berk => vec![Lunch, Dinner, LateNight],
-            Worcester => vec![Lunch, Dinner, LateNight],
-            Hamp | Frank => vec![Lunch, Dinner],
-        },
-    }
-}
-
-pub fn get_food_on_menus(food: &str, store: &FoodStore) -> Result<Vec<String>> {
-    let mut places: Vec<String> = vec![];
-
-    for dining_common in &[Berk, Hamp, Frank, Worcester] {
-        let meals = which_meals(*dining_common);
-        for meal in meals {
-            let food_on_menu = get_on_menu(*dining_common, meal, food, &store)?;
-            if !food_on_menu.is_empty() {
-                places.push(
-                    format!("{:?} {}: {}", dining_common, meal, food_on_menu.join(", "))
-                        .to_string(),
+            if added.is_empty() && removed.is_empty() {
+                None
+            } else {
+                Some((meal, added, removed))
+            }
+        })
+        .collect()
+}
+
+impl InternalFoodStore {
+    fn save_changes(&self) {
+        if let Err(err) = write_changes(self) {
+            println!("Couldn't save the menu changes: {}", err);
+        }
+    }
+
+    fn record_changes(
+        &mut self,
+        date: Date<FixedOffset>,
+        old: &DiningCommonsMenus,
+        new: &DiningCommonsMenus,
+    ) {
+        for new_menu in new.all() {
+            let old_menu = match old.menus.get(&new_menu.dining_common) {
+                Some(old_menu) => old_menu,
+                None => continue,
+            };
+
+            for (meal, added, removed) in diff_menus(old_menu, new_menu) {
+                println!(
+                    "{} {} changed on {}: added {:?}, removed {:?}",
+                    new_menu.dining_common, meal, date, added, removed
                 );
+                self.changes.push(MenuChange {
+                    id: self.next_change_id,
+                    date: format_date(date),
+                    dining_common: new_menu.dining_common.clone(),
+                    meal: meal.to_string(),
+                    added,
+                    removed,
+                });
+                self.next_change_id += 1;
             }
         }
     }
+}
+
+// Fetches a day's menus and swaps them into the store, noting anything that changed
+//  The store isn't locked while waiting on the dining site, so requests can still be answered
+pub fn refresh_menus(store: &FoodStore, date: Date<FixedOffset>) {
+    let (previous, dining_commons, history) = {
+        let store = store.lock().unwrap();
+        (
+            store.menus.get(&date).cloned(),
+            store.dining_commons.clone(),
+            store.history.clone(),
+        )
+    };
+
+    let mut menus = previous.clone().unwrap_or_default();
+    update_menus(&mut menus, &dining_commons, date);
+    archive(date, &menus, &history);
+
+    let mut store = store.lock().unwrap();
+    if let Some(previous) = previous {
+        let next_change_id = store.next_change_id;
+        store.record_changes(date, &previous, &menus);
+        if store.next_change_id != next_change_id {
+            store.save_changes();
+        }
+    }
+    store.menus.insert(date, menus);
+}
+
+// The changes since the given one that involve the food at the place, or just the latest change
+//  id if no change was given
+pub fn get_changes(
+    food: &str,
+    place: &PlaceFilter,
+    since: Option<u64>,
+    store: &FoodStore,
+) -> MenuChanges {
+    let store = store.lock().unwrap();
+    let latest = store.next_change_id;
+
+    let since = match since {
+        Some(since) => since,
+        None => {
+            return MenuChanges {
+                latest,
+                changes: vec![],
+            }
+        }
+    };
+
+    // An empty search is told about every change
+    let matcher = FoodMatcher::from_search(food);
+    let matching = |dishes: &[String]| -> Vec<String> {
+        dishes
+            .iter()
+            .filter(|dish| {
+                matcher
+                    .as_ref()
+                    .map_or(true, |matcher| matcher.score(dish).is_some())
+            })
+            .cloned()
+            .collect()
+    };
+
+    let changes = store
+        .changes
+        .iter()
+        .filter(|change| change.id >= since)
+        .filter(|change| {
+            place.dining_common.as_ref().map_or(true, |dining_common| {
+                dining_common.name == change.dining_common
+            }) && place.meal.map_or(true, |meal| {
+                meal_from_name(&change.meal).map_or(false, |changed| changed.matches(meal))
+            })
+        })
+        .map(|change| MenuChange {
+            added: matching(&change.added),
+            removed: matching(&change.removed),
+            ..change.clone()
+        })
+        .filter(|change| !change.added.is_empty() || !change.removed.is_empty())
+        .collect();
+
+    MenuChanges { latest, changes }
+}
+
+// The cached days missing some dining common's menu, for the refresher to try again
+pub fn incomplete_dates(store: &FoodStore) -> Vec<Date<FixedOffset>> {
+    let store = store.lock().unwrap();
+    store
+        .menus
+        .iter()
+        .filter(|(_, menus)| !menus.unavailable(&store.dining_commons).is_empty())
+        .map(|(date, _)| *date)
+        .collect()
+}
+
+// Drops cached menus that are too old to be asked about often, or too far off to be right yet
+pub fn prune_store(store: &FoodStore) {
+    let today = get_datetime().date();
+    let oldest_kept = today - chrono::Duration::days(DAYS_TO_KEEP);
+    let newest_kept = today + chrono::Duration::days(MAX_DAYS_AHEAD);
+    let oldest_kept_string = format_date(oldest_kept);
+
+    let mut store = store.lock().unwrap();
+    store
+        .menus
+        .retain(|cached_date, _| *cached_date >= oldest_kept && *cached_date <= newest_kept);
+    // The dates are formatted year first, so comparing them as strings works
+    let change_count = store.changes.len();
+    store
+        .changes
+        .retain(|change| change.date >= oldest_kept_string);
+    if store.changes.len() != change_count {
+        store.save_changes();
+    }
+}
+
+// Whether the dining site won't have the day's menus yet
+pub fn is_too_far_ahead(date: Date<FixedOffset>) -> bool {
+    date > get_datetime().date() + chrono::Duration::days(MAX_DAYS_AHEAD)
+}
+
+// Every dining common's menu for a day, fetched at most once
+//  None if the day is too far off
+fn get_menus(date: Date<FixedOffset>, store: &FoodStore) -> Option<DiningCommonsMenus> {
+    if is_too_far_ahead(date) {
+        return None;
+    }
+
+    let (dining_commons, history) = {
+        let store = store.lock().unwrap();
+        if let Some(menus) = store.menus.get(&date) {
+            return Some(menus.clone());
+        }
+        (store.dining_commons.clone(), store.history.clone())
+    };
+
+    // The refresher keeps the coming week cached, so this is only for older or farther off days
+    //  Past days come from the archive if they're in it, while anything else has to be fetched
+    let archived = if date < get_datetime().date() {
+        load_archived_menus(date)
+    } else {
+        None
+    };
+    let menus = match archived {
+        Some(menus) => menus,
+        None => {
+            let menus = get_menus_no_cache(&dining_commons, date);
+            archive(date, &menus, &history);
+            menus
+        }
+    };
+
+    // Any dining common that couldn't be fetched is tried again by the refresher
+    Some(
+        store
+            .lock()
+            .unwrap()
+            .menus
+            .entry(date)
+            .or_insert(menus)
+            .clone(),
+    )
+}
+
+// None if the dining common's menu couldn't be fetched, or the day is too far off
+fn get_menu(
+    dining_common: &DiningCommon,
+    date: Date<FixedOffset>,
+    store: &FoodStore,
+) -> Option<DiningCommonMenu> {
+    get_menus(date, store)?.get(dining_common).cloned()
+}
+
+// The dishes matching the search at one dining common and meal, best matches first
+//  No matcher means every dish that passes the filter
+pub fn get_on_menu(
+    menu: &DiningCommonMenu,
+    meal: Meal,
+    matcher: Option<&FoodMatcher>,
+    filter: &DietFilter,
+) -> Vec<(f64, Dish)> {
+    let meal_menu = match menu.meal(meal) {
+        Some(meal_menu) => meal_menu,
+        None => {
+            println!(
+                "Tried to find food at {} {:?} but failed to parse page",
+                menu.dining_common, meal
+            );
+            return vec![];
+        }
+    };
+
+    let mut filtered: Vec<(f64, Dish)> = meal_menu
+        .dishes()
+        .filter(|dish| filter.allows(dish))
+        .filter_map(|dish| {
+            let score = match matcher {
+                Some(matcher) => matcher.score(&dish.name)?,
+                None => 0.0,
+            };
+            Some((score, dish.clone()))
+        })
+        .collect();
+    filtered.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
 
-    Ok(places)
+    let found: Vec<&str> = filtered
+        .iter()
+        .map(|(_, dish)| dish.name.as_str())
+        .collect();
+    if !found.is_empty() {
+        println!("{}", found.join(" "));
+    }
+
+    filtered
+}
+
+// The meals the four dining commons usually serve on each day of the week
+//  Only used when a menu page couldn't be fetched or parsed, and None for other locations
+fn which_meals(dc: &DiningCommon, date: Date<FixedOffset>) -> Option<Vec<Meal>> {
+    Some(match (dc.code.as_str(), date.weekday()) {
+        ("berkshire", Sat) | ("berkshire", Sun) => vec![Brunch, Dinner, LateNight],
+        ("berkshire", _) => vec![Lunch, Dinner, LateNight, GrabAndGo],
+        ("hampshire", Sat) | ("hampshire", Sun) | ("franklin", Sat) | ("franklin", Sun) => {
+            vec![Brunch, Dinner]
+        }
+        ("hampshire", _) | ("franklin", _) => vec![Breakfast, Lunch, Dinner, GrabAndGo],
+        ("worcester", Fri) => vec![Breakfast, Lunch, Dinner, GrabAndGo],
+        ("worcester", Sat) => vec![Brunch, Dinner],
+        ("worcester", Sun) => vec![Brunch, Dinner, LateNight],
+        ("worcester", _) => vec![Breakfast, Lunch, Dinner, LateNight, GrabAndGo],
+        _ => return None,
+    })
+}
+
+// The meals a dining common serves on a day, going by the meals on that day's menu page
+//  so that breaks and schedule changes are picked up without changing the table above.
+//  A menu page without any meals means the dining common is closed.
+fn meals_served(
+    menu: Option<&DiningCommonMenu>,
+    dc: &DiningCommon,
+    date: Date<FixedOffset>,
+) -> Vec<Meal> {
+    match menu {
+        Some(menu) => menu.meals.iter().map(|meal_menu| meal_menu.meal).collect(),
+        None => which_meals(dc, date).unwrap_or_default(),
+    }
+}
+
+// The full menu for one dining common and meal, one line per station
+pub fn get_full_menu(
+    dining_common: &DiningCommon,
+    meal: Meal,
+    date: Date<FixedOffset>,
+    store: &FoodStore,
+) -> Result<String> {
+    if is_too_far_ahead(date) {
+        return Ok(TOO_FAR_AHEAD.to_string());
+    }
+
+    let menu = get_menu(dining_common, date, store);
+    let serves_meal = meals_served(menu.as_ref(), dining_common, date)
+        .iter()
+        .any(|served| served.matches(meal));
+    let meal_menu = match menu.as_ref().and_then(|menu| menu.meal(meal)) {
+        Some(meal_menu) => meal_menu,
+        None if !serves_meal => {
+            return Ok(format!(
+                "{} doesn't serve {} on {}",
+                dining_common,
+                meal,
+                date.format("%A, %B %-d")
+            ))
+        }
+        None if menu.is_none() => {
+            return Ok(format!("Couldn't get the menu for {}", dining_common))
+        }
+        None => {
+            return Ok(format!(
+                "Couldn't find the {} menu for {}",
+                meal, dining_common
+            ))
+        }
+    };
+
+    let stations: Vec<String> = meal_menu
+        .stations
+        .iter()
+        .map(|station| {
+            let dishes: Vec<&str> = station
+                .dishes
+                .iter()
+                .map(|dish| dish.name.as_str())
+                .collect();
+            match station.name.as_str() {
+                "" => dishes.join(", "),
+                name => format!("{}: {}", name, dishes.join(", ")),
+            }
+        })
+        .collect();
+
+    Ok(format!(
+        "{} {}: \n{}",
+        dining_common,
+        meal,
+        stations.join("\n")
+    ))
+}
+
+pub fn get_food_on_menus(
+    food: &str,
+    date: Date<FixedOffset>,
+    filter: &DietFilter,
+    place: &PlaceFilter,
+    store: &FoodStore,
+) -> Result<FoodSearch> {
+    // An empty search lists every dish, so that filters can be used on their own
+    let matcher = FoodMatcher::from_search(food);
+    // Each place is kept with the score of its best match, so the closest matches come first
+    let mut places: Vec<(f64, MenuMatch)> = vec![];
+    let mut unavailable: Vec<String> = vec![];
+    // Nothing to search if the day is too far off
+    let menus = get_menus(date, store).unwrap_or_default();
+
+    for dining_common in dining_commons(store)
+        .iter()
+        .filter(|dining_common| place.allows_dining_common(dining_common))
+    {
+        let menu = match menus.get(dining_common) {
+            Some(menu) => menu,
+            None if is_too_far_ahead(date) => continue,
+            None => {
+                unavailable.push(dining_common.name.clone());
+                continue;
+            }
+        };
+
+        for meal in meals_served(Some(menu), dining_common, date)
+            .into_iter()
+            .filter(|meal| place.allows_meal(*meal))
+        {
+            let food_on_menu = get_on_menu(menu, meal, matcher.as_ref(), filter);
+            if let Some((best_score, _)) = food_on_menu.first() {
+                places.push((
+                    *best_score,
+                    MenuMatch {
+                        dining_common: dining_common.name.clone(),
+                        meal: meal.to_string(),
+                        dishes: food_on_menu
+                            .iter()
+                            .map(|(_, dish)| dish.to_match())
+                            .collect(),
+                    },
+                ));
+            }
+        }
+    }
+
+    places.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+
+    Ok(FoodSearch {
+        food: food.to_string(),
+        diets: filter.diets.clone(),
+        excluded_allergens: filter.excluded_allergens.clone(),
+        date: format_date(date),
+        found: places.into_iter().map(|(_, place)| place).collect(),
+        unavailable,
+        too_far_ahead: is_too_far_ahead(date),
+        dining_common: place
+            .dining_common
+            .as_ref()
+            .map(|dining_common| dining_common.name.clone()),
+        meal: place.meal.map(|meal| meal.to_string()),
+    })
+}
+
+// The closest matching dish on a day's menus, along with the name of the dining common serving it
+pub fn find_dish(
+    dish_name: &str,
+    date: Date<FixedOffset>,
+    place: &PlaceFilter,
+    store: &FoodStore,
+) -> Option<(String, Dish)> {
+    let matcher = FoodMatcher::new(dish_name);
+    let mut best_match: Option<(f64, String, Dish)> = None;
+
+    let menus = get_menus(date, store)?;
+    for menu in dining_commons(store)
+        .iter()
+        .filter(|dining_common| place.allows_dining_common(dining_common))
+        .filter_map(|dining_common| menus.get(dining_common))
+    {
+        for dish in menu
+            .meals
+            .iter()
+            .filter(|meal_menu| place.allows_meal(meal_menu.meal))
+            .flat_map(|meal_menu| meal_menu.dishes())
+        {
+            if let Some(score) = matcher.score(&dish.name) {
+                if best_match
+                    .as_ref()
+                    .map_or(true, |(best, _, _)| score > *best)
+                {
+                    best_match = Some((score, menu.dining_common.clone(), dish.clone()));
+                }
+            }
+        }
+    }
+
+    best_match.map(|(_, dining_common, dish)| (dining_common, dish))
+}
+
+// Every dish a dining common serves for a meal on a day
+//  None if its menu couldn't be fetched or it doesn't serve that meal
+pub fn dishes_served(
+    dining_common: &DiningCommon,
+    meal: Meal,
+    date: Date<FixedOffset>,
+    store: &FoodStore,
+) -> Option<Vec<Dish>> {
+    let menu = get_menu(dining_common, date, store)?;
+    let meal_menu = menu.meal(meal)?;
+    Some(meal_menu.dishes().cloned().collect())
+}
+
+// Finds the nutrition facts for the closest matching dish on a day's menus
+pub fn get_nutrition(
+    dish_name: &str,
+    date: Date<FixedOffset>,
+    store: &FoodStore,
+) -> Result<Option<Nutrition>> {
+    Ok(
+        find_dish(dish_name, date, &PlaceFilter::default(), store)
+            .map(|(_, dish)| dish.nutrition()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dining_commons() -> Vec<DiningCommon> {
+        [
+            ("Berk", "berkshire"),
+            ("Worcester", "worcester"),
+            ("Blue Wall", "blue-wall"),
+        ]
+        .iter()
+        .map(|(name, code)| DiningCommon {
+            name: name.to_string(),
+            code: code.to_string(),
+        })
+        .collect()
+    }
+
+    fn place(dining_common: &str, meal: Option<&str>) -> Option<(String, Option<Meal>)> {
+        find_place(
+            dining_common,
+            meal.map(|meal| meal.to_string()).as_ref(),
+            &dining_commons(),
+        )
+        .map(|(dining_common, meal)| (dining_common.name, meal))
+    }
+
+    #[test]
+    fn finds_names_with_more_than_one_word() {
+        assert_eq!(
+            place("blue", Some("wall")),
+            Some(("Blue Wall".to_string(), None))
+        );
+        assert_eq!(
+            place("blue", Some("wall dinner")),
+            Some(("Blue Wall".to_string(), Some(Dinner)))
+        );
+    }
+
+    #[test]
+    fn splits_off_the_meal() {
+        assert_eq!(
+            place("berk", Some("dinner")),
+            Some(("Berk".to_string(), Some(Dinner)))
+        );
+        assert_eq!(
+            place("worcester", Some("late night")),
+            Some(("Worcester".to_string(), Some(LateNight)))
+        );
+        assert_eq!(place("berk", None), Some(("Berk".to_string(), None)));
+    }
+
+    #[test]
+    fn rejects_unknown_places() {
+        assert_eq!(place("hamp", Some("dinner")), None);
+        assert_eq!(place("berk", Some("second breakfast")), None);
+    }
 }
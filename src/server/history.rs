@@ -0,0 +1,310 @@
+use crate::food::{DiningCommon, DiningCommonsMenus, Meal};
+use crate::matching::FoodMatcher;
+
+use chrono::offset::FixedOffset;
+use chrono::Date;
+use chrono::Datelike;
+use chrono::Weekday;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+use umass_bot_common::datetime::{format_date, parse_date};
+use umass_bot_common::error::*;
+
+// Each day's menus are saved as "menu_history/YYYY-MM-DD.json"
+static HISTORY_DIRECTORY: &str = "menu_history";
+
+// The dishes that one dining common served for a meal on an archived day
+struct ArchivedMeal {
+    dining_common: String,
+    meal: Meal,
+    dishes: Vec<String>,
+}
+
+// The names of the dishes on every archived day's menus, so searching the history doesn't have to
+//  read the whole archive each time
+#[derive(Default)]
+pub struct History {
+    days: BTreeMap<Date<FixedOffset>, Vec<ArchivedMeal>>,
+}
+
+pub type HistoryStore = Arc<RwLock<History>>;
+
+impl History {
+    fn add(&mut self, date: Date<FixedOffset>, menus: &DiningCommonsMenus) {
+        let meals = menus
+            .all()
+            .iter()
+            .flat_map(|menu| {
+                menu.meals.iter().map(move |meal_menu| ArchivedMeal {
+                    dining_common: menu.dining_common.clone(),
+                    meal: meal_menu.meal,
+                    dishes: meal_menu.dishes().map(|dish| dish.name.clone()).collect(),
+                })
+            })
+            .collect();
+        self.days.insert(date, meals);
+    }
+
+    // How many days there are from the first archived day through the last one, including any
+    //  that are missing from the archive
+    fn days_covered(&self) -> i64 {
+        match (self.days.keys().next(), self.days.keys().next_back()) {
+            (Some(first), Some(last)) => (*last - *first).num_days() + 1,
+            _ => 0,
+        }
+    }
+}
+
+// Reads the whole archive once, when the server starts
+pub fn load_history() -> HistoryStore {
+    let mut history = History::default();
+    match load_archive() {
+        Ok(archive) => {
+            for (date, menus) in archive {
+                history.add(date, &menus);
+            }
+        }
+        Err(err) => println!("Couldn't read the menu archive: {}", err),
+    }
+
+    Arc::new(RwLock::new(history))
+}
+
+pub fn archive_menus(
+    date: Date<FixedOffset>,
+    menus: &DiningCommonsMenus,
+    history: &HistoryStore,
+) -> Result<()> {
+    history.write().unwrap().add(date, menus);
+
+    fs::create_dir_all(HISTORY_DIRECTORY)?;
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(archive_path(date))?
+        .write_all(serde_json::to_string(menus)?.as_bytes())?;
+
+    Ok(())
+}
+
+fn archive_path(date: Date<FixedOffset>) -> String {
+    format!("{}/{}.json", HISTORY_DIRECTORY, format_date(date))
+}
+
+pub fn load_archived_menus(date: Date<FixedOffset>) -> Option<DiningCommonsMenus> {
+    let menus = fs::read_to_string(archive_path(date)).ok()?;
+    serde_json::from_str(&menus).ok()
+}
+
+// Every archived day's menus
+fn load_archive() -> Result<Vec<(Date<FixedOffset>, DiningCommonsMenus)>> {
+    let mut archive: Vec<(Date<FixedOffset>, DiningCommonsMenus)> = vec![];
+
+    if fs::metadata(HISTORY_DIRECTORY).is_err() {
+        return Ok(archive);
+    }
+
+    for entry in fs::read_dir(HISTORY_DIRECTORY)? {
+        let path = entry?.path();
+        let date = match path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(parse_date)
+        {
+            Some(date) => date,
+            None => continue,
+        };
+
+        match serde_json::from_str(&fs::read_to_string(&path)?) {
+            Ok(menus) => archive.push((date, menus)),
+            Err(err) => println!("Couldn't read archived menus {:?}: {}", path, err),
+        }
+    }
+
+    Ok(archive)
+}
+
+// A time that a searched for food was served
+struct Serving {
+    date: Date<FixedOffset>,
+    // The dining common's name
+    dining_common: String,
+    description: String,
+}
+
+// Every time the food was served in the archive, most recent first, along with how many days the
+//  archive covers
+fn find_servings(
+    history: &HistoryStore,
+    food: &str,
+    dining_common: Option<&DiningCommon>,
+) -> (i64, Vec<Serving>) {
+    let matcher = FoodMatcher::new(food);
+    let history = history.read().unwrap();
+
+    let mut servings: Vec<Serving> = vec![];
+    for (date, meals) in history.days.iter().rev() {
+        for meal in meals {
+            if dining_common.map_or(false, |dining_common| {
+                dining_common.name != meal.dining_common
+            }) {
+                continue;
+            }
+
+            if let Some(dish) = meal
+                .dishes
+                .iter()
+                .find(|dish| matcher.score(dish).is_some())
+            {
+                servings.push(Serving {
+                    date: *date,
+                    dining_common: meal.dining_common.clone(),
+                    description: format!("{} {} ({})", meal.dining_common, meal.meal, dish),
+                });
+            }
+        }
+    }
+
+    (history.days_covered(), servings)
+}
+
+fn describe_place(dining_common: Option<&DiningCommon>) -> String {
+    match dining_common {
+        Some(dining_common) => format!(" at {}", dining_common),
+        None => String::new(),
+    }
+}
+
+pub fn last_served(
+    history: &HistoryStore,
+    food: &str,
+    dining_common: Option<&DiningCommon>,
+) -> String {
+    let (_, servings) = find_servings(history, food, dining_common);
+
+    match servings.first() {
+        Some(serving) => format!(
+            "{} was last served on {}: {}",
+            food,
+            serving.date.format("%A, %B %-d"),
+            serving.description
+        ),
+        None => format!(
+            "{} hasn't been served{} since the archive started",
+            food,
+            describe_place(dining_common)
+        ),
+    }
+}
+
+// The distinct days each dining common served the food on
+fn days_served(servings: Vec<Serving>) -> HashMap<String, Vec<Date<FixedOffset>>> {
+    let mut days_served: HashMap<String, Vec<Date<FixedOffset>>> = HashMap::new();
+    for serving in servings {
+        let days = days_served
+            .entry(serving.dining_common)
+            .or_insert_with(|| vec![]);
+        if !days.contains(&serving.date) {
+            days.push(serving.date);
+        }
+    }
+    days_served
+}
+
+// How many of the days since the archive started each dining common served the food on
+pub fn frequency(
+    history: &HistoryStore,
+    food: &str,
+    dining_common: Option<&DiningCommon>,
+) -> String {
+    let (days_archived, servings) = find_servings(history, food, dining_common);
+    let days_served = days_served(servings);
+
+    if days_served.is_empty() {
+        return format!(
+            "{} wasn't served{} in the last {} days",
+            food,
+            describe_place(dining_common),
+            days_archived
+        );
+    }
+
+    let mut counts: Vec<(String, usize)> = days_served
+        .into_iter()
+        .map(|(dining_common, days)| (dining_common, days.len()))
+        .collect();
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    let lines: Vec<String> = counts
+        .iter()
+        .map(|(dining_common, count)| {
+            format!("{}: {} of {} days", dining_common, count, days_archived)
+        })
+        .collect();
+
+    format!("{}: \n{}", food, lines.join("\n"))
+}
+
+// Which days of the week each dining common tends to serve the food on
+pub fn weekdays(
+    history: &HistoryStore,
+    food: &str,
+    dining_common: Option<&DiningCommon>,
+) -> String {
+    let (_, servings) = find_servings(history, food, dining_common);
+    let days_served = days_served(servings);
+
+    if days_served.is_empty() {
+        return format!(
+            "{} hasn't been served{} since the archive started",
+            food,
+            describe_place(dining_common)
+        );
+    }
+
+    let mut lines: Vec<String> = days_served
+        .into_iter()
+        .map(|(dining_common, days)| {
+            let mut counts: Vec<(Weekday, usize)> = vec![];
+            for day in &days {
+                match counts
+                    .iter_mut()
+                    .find(|(weekday, _)| *weekday == day.weekday())
+                {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((day.weekday(), 1)),
+                }
+            }
+            // Most common first, then in order through the week
+            counts.sort_by(|(a_day, a), (b_day, b)| {
+                b.cmp(a).then(
+                    a_day
+                        .num_days_from_monday()
+                        .cmp(&b_day.num_days_from_monday()),
+                )
+            });
+
+            let count_descriptions: Vec<String> = counts
+                .iter()
+                .map(|(weekday, count)| format!("{:?} {}", weekday, count))
+                .collect();
+            format!(
+                "{}: usually {:?} ({} out of {} days served)",
+                dining_common,
+                counts[0].0,
+                count_descriptions.join(", "),
+                days.len()
+            )
+        })
+        .collect();
+    lines.sort();
+
+    format!("{}: \n{}", food, lines.join("\n"))
+}
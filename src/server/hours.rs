@@ -0,0 +1,239 @@
+// When each location is open, from hours.json
+//
+// Each schedule gives the hours for some locations on some days of the week. Schedules with a
+// date range (breaks, finals week, holidays) are listed after the regular hours, and the last
+// schedule that covers a day is the one that's used.
+
+use crate::food::{DiningCommon, Meal};
+
+use chrono::offset::FixedOffset;
+use chrono::Date;
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::NaiveTime;
+use chrono::Weekday;
+
+use serde::Deserialize;
+
+use std::fs::File;
+use std::io::Read;
+
+use umass_bot_common::datetime::{format_time, parse_date};
+
+// Times are like "07:00" or "21:30"
+static TIME_FORMAT: &str = "%H:%M";
+
+#[derive(Clone, Debug, Deserialize)]
+struct Period {
+    // Locations without meals, like the retail ones, just have a single period
+    #[serde(default)]
+    meal: Option<Meal>,
+    open: String,
+    // Closing before the opening time means closing after midnight
+    close: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Schedule {
+    // Like "Finals week", for schedules that replace the regular hours
+    #[serde(default)]
+    name: Option<String>,
+    // The locations' names
+    locations: Vec<String>,
+    // Like "Mon" or "Sat", or every day if empty
+    #[serde(default)]
+    days: Vec<String>,
+    // The first and last dates that the schedule is used, or always if missing
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    to: Option<String>,
+    // A location that's closed all day has no periods
+    periods: Vec<Period>,
+}
+
+pub type HoursStore = Vec<Schedule>;
+
+// Get the hours json file from memory
+pub fn load_hours() -> HoursStore {
+    let mut hours_json = String::new();
+    let _ = File::open("hours.json")
+        .expect("No hours json file")
+        .read_to_string(&mut hours_json);
+
+    serde_json::from_str(hours_json.trim()).expect("Invalid hours json file")
+}
+
+impl Schedule {
+    fn covers(&self, dining_common: &DiningCommon, date: Date<FixedOffset>) -> bool {
+        let on_day = self.days.is_empty()
+            || self
+                .days
+                .iter()
+                .any(|day| day.parse::<Weekday>().ok() == Some(date.weekday()));
+        let started = self.from.as_ref().map_or(true, |from| {
+            parse_date(from).map_or(false, |from| from <= date)
+        });
+        let not_ended = self
+            .to
+            .as_ref()
+            .map_or(true, |to| parse_date(to).map_or(false, |to| date <= to));
+
+        self.locations.contains(&dining_common.name) && on_day && started && not_ended
+    }
+}
+
+fn schedule_on<'a>(
+    hours: &'a HoursStore,
+    dining_common: &DiningCommon,
+    date: Date<FixedOffset>,
+) -> Option<&'a Schedule> {
+    hours
+        .iter()
+        .rev()
+        .find(|schedule| schedule.covers(dining_common, date))
+}
+
+pub struct OpenPeriod {
+    pub meal: Option<Meal>,
+    pub open: DateTime<FixedOffset>,
+    pub close: DateTime<FixedOffset>,
+}
+
+fn open_periods(schedule: &Schedule, date: Date<FixedOffset>) -> Vec<OpenPeriod> {
+    schedule
+        .periods
+        .iter()
+        .filter_map(|period| {
+            let open = NaiveTime::parse_from_str(&period.open, TIME_FORMAT).ok()?;
+            let close = NaiveTime::parse_from_str(&period.close, TIME_FORMAT).ok()?;
+            let close_date = if close <= open {
+                date + chrono::Duration::days(1)
+            } else {
+                date
+            };
+
+            Some(OpenPeriod {
+                meal: period.meal,
+                open: date.and_time(open)?,
+                close: close_date.and_time(close)?,
+            })
+        })
+        .collect()
+}
+
+pub fn describe_meal(meal: Option<Meal>) -> String {
+    match meal {
+        Some(meal) => format!(" {}", meal),
+        None => String::new(),
+    }
+}
+
+// What a location is serving at a time, along with the day whose menu it's from
+pub fn open_period(
+    hours: &HoursStore,
+    dining_common: &DiningCommon,
+    time: DateTime<FixedOffset>,
+) -> Option<(Date<FixedOffset>, OpenPeriod)> {
+    // Late night from the day before can still be going after midnight
+    let days = [time.date() - chrono::Duration::days(1), time.date()];
+
+    days.iter()
+        .filter_map(|day| Some((*day, schedule_on(hours, dining_common, *day)?)))
+        .flat_map(|(day, schedule)| {
+            open_periods(schedule, day)
+                .into_iter()
+                .map(move |period| (day, period))
+        })
+        .find(|(_, period)| period.open <= time && time < period.close)
+}
+
+// Which locations are open at a time, and until when
+pub fn open_at(
+    hours: &HoursStore,
+    dining_commons: &[DiningCommon],
+    time: DateTime<FixedOffset>,
+) -> String {
+    let open: Vec<String> = dining_commons
+        .iter()
+        .filter_map(|dining_common| {
+            let (_, period) = open_period(hours, dining_common, time)?;
+
+            Some(format!(
+                "{}{} until {}",
+                dining_common,
+                describe_meal(period.meal),
+                format_time(period.close.time())
+            ))
+        })
+        .collect();
+
+    let when = format!(
+        "at {} on {}",
+        format_time(time.time()),
+        time.format("%A, %B %-d")
+    );
+    if open.is_empty() {
+        format!("Nothing is open {}", when)
+    } else {
+        format!("Open {}: \n{}", when, open.join("\n"))
+    }
+}
+
+// When a location is open on a day, or just when it serves the given meal
+pub fn hours_on(
+    hours: &HoursStore,
+    dining_common: &DiningCommon,
+    meal: Option<Meal>,
+    date: Date<FixedOffset>,
+) -> String {
+    let day = date.format("%A, %B %-d");
+
+    let schedule = match schedule_on(hours, dining_common, date) {
+        Some(schedule) => schedule,
+        None => return format!("Don't know the hours for {} on {}", dining_common, day),
+    };
+
+    let periods: Vec<String> = open_periods(schedule, date)
+        .iter()
+        .filter(|period| {
+            meal.map_or(true, |meal| {
+                period.meal.map_or(false, |served| served.matches(meal))
+            })
+        })
+        .map(|period| {
+            let times = format!(
+                "{} to {}",
+                format_time(period.open.time()),
+                format_time(period.close.time())
+            );
+            match period.meal {
+                Some(meal) => format!("{}: {}", meal, times),
+                None => times,
+            }
+        })
+        .collect();
+
+    let name = match &schedule.name {
+        Some(name) => format!(" ({})", name),
+        None => String::new(),
+    };
+
+    if periods.is_empty() {
+        format!(
+            "{}{} isn't open on {}{}",
+            dining_common,
+            describe_meal(meal),
+            day,
+            name
+        )
+    } else {
+        format!(
+            "{} on {}{}: \n{}",
+            dining_common,
+            day,
+            name,
+            periods.join("\n")
+        )
+    }
+}
@@ -6,51 +6,555 @@ extern crate rocket;
 extern crate rocket_contrib;
 extern crate umass_bot_common;
 
+use umass_bot_common::datetime::{get_datetime, parse_date, parse_time};
 use umass_bot_common::error::*;
+use umass_bot_common::food::{FoodSearch, MenuChanges, Nutrition};
 
+mod buildings;
 mod events;
 mod food;
+mod history;
+mod hours;
+mod matching;
+mod plates;
+mod ratings;
+mod recommend;
+mod refresh;
 mod rooms;
 
 use crate::rooms::Section;
+use buildings::BuildingIndex;
+use food::DietFilter;
+use food::DiningCommon;
 use food::FoodStore;
+use food::PlaceFilter;
+use hours::HoursStore;
+use plates::{PlateStore, Target};
+use ratings::RatingStore;
 
 use rocket::State;
 use rooms::load_sections_map;
+use rooms::ClassStore;
 use rooms::RoomStore;
 
 use rocket_contrib::json::Json;
 
+use chrono::offset::FixedOffset;
+use chrono::Date;
+use chrono::Weekday;
+
+// Dates are optional on the food routes and default to today
+//  None if the date was given but couldn't be parsed
+fn get_date(date: Option<String>) -> Option<Date<FixedOffset>> {
+    match date {
+        Some(date) => parse_date(&date),
+        None => Some(get_datetime().date()),
+    }
+}
+
 #[get("/?<input>")]
 fn echo(input: String) -> String {
     input
 }
 
 #[get("/?<room>")]
-fn room(room_store: State<RoomStore>, room: String) -> Option<Json<Vec<Section>>> {
-    if !room_store.contains_key(&room) {
-        None
-    } else {
-        Some(Json(room_store.get(&room).unwrap().to_vec()))
+fn room(
+    room_store: State<RoomStore>,
+    building_index: State<BuildingIndex>,
+    room: String,
+) -> Option<Json<Vec<Section>>> {
+    let room = building_index.find_room(&room)?;
+    Some(Json(room_store.get(room)?.to_vec()))
+}
+
+// A room's classes for the week, or just for one day, like "tuesday"
+#[get("/schedule?<room>&<day>")]
+fn room_schedule(
+    room_store: State<RoomStore>,
+    building_index: State<BuildingIndex>,
+    room: String,
+    day: Option<String>,
+) -> Option<String> {
+    let day = match day {
+        Some(day) => Some(day.parse::<Weekday>().ok()?),
+        None => None,
+    };
+
+    rooms::schedule(&room_store, building_index.find_room(&room)?, day)
+}
+
+// Whether a room is taken at a time, or right now if no time is given, and until when
+#[get("/status?<room>&<at>&<date>")]
+fn room_status(
+    room_store: State<RoomStore>,
+    building_index: State<BuildingIndex>,
+    room: String,
+    at: Option<String>,
+    date: Option<String>,
+) -> Option<String> {
+    let date = get_date(date)?;
+    let time = match at {
+        Some(at) => parse_time(&at)?,
+        None => get_datetime().time(),
+    };
+
+    rooms::room_status(
+        &room_store,
+        building_index.find_room(&room)?,
+        date.and_time(time)?,
+    )
+}
+
+// Rooms without a class at a time, or right now if no time is given
+#[get("/free?<at>&<date>&<building>&<minutes>")]
+fn free_rooms(
+    room_store: State<RoomStore>,
+    building_index: State<BuildingIndex>,
+    at: Option<String>,
+    date: Option<String>,
+    building: Option<String>,
+    minutes: Option<i64>,
+) -> Option<String> {
+    let date = get_date(date)?;
+    let time = match at {
+        Some(at) => parse_time(&at)?,
+        None => get_datetime().time(),
+    };
+    let building = match building {
+        Some(building) => Some(building_index.find_building(&building)?),
+        None => None,
+    };
+
+    Some(rooms::free_rooms(
+        &room_store,
+        &building_index,
+        date.and_time(time)?,
+        building,
+        minutes.unwrap_or(0),
+    ))
+}
+
+// Courses by subject and number, like "COMPSCI 187", or by part of their title
+#[get("/?<search>")]
+fn class(class_store: State<ClassStore>, search: String) -> Option<String> {
+    rooms::search_classes(&class_store, &search)
+}
+
+// Every building with how many rooms it has, or the rooms in one building
+#[get("/buildings?<building>")]
+fn buildings(building_index: State<BuildingIndex>, building: Option<String>) -> Option<String> {
+    match building {
+        Some(building) => Some(building_index.find_building(&building)?.describe_rooms()),
+        None => Some(building_index.describe_buildings()),
     }
 }
 
-#[get("/?<food>")]
-fn food(food_store: State<FoodStore>, food: String) -> Result<String> {
-    let places_found = food::get_food_on_menus(&food, &food_store)?;
+// The dining common and meal filters on the food routes are optional
+//  None if either was given but couldn't be found
+fn get_place(
+    food_store: &FoodStore,
+    dining_common: Option<String>,
+    meal: Option<String>,
+) -> Option<PlaceFilter> {
+    PlaceFilter::from_params(dining_common, meal, &food::dining_commons(food_store))
+}
+
+fn search_food(
+    food_store: &FoodStore,
+    food: String,
+    diet: Option<String>,
+    exclude: Option<String>,
+    date: Option<String>,
+    dining_common: Option<String>,
+    meal: Option<String>,
+) -> Result<Option<FoodSearch>> {
+    let (date, place) = match (get_date(date), get_place(food_store, dining_common, meal)) {
+        (Some(date), Some(place)) => (date, place),
+        _ => return Ok(None),
+    };
+    let filter = DietFilter::from_params(diet, exclude);
 
-    Ok(match places_found.len() {
-        0 => format!("{} not found", food).to_string(),
-        _ => format!("{}: \n{}", food, places_found.join("\n")).to_string(),
-    })
+    Ok(Some(food::get_food_on_menus(
+        &food, date, &filter, &place, food_store,
+    )?))
+}
+
+fn add_ratings(mut search: FoodSearch, rating_store: &RatingStore) -> FoodSearch {
+    ratings::add_ratings(&mut search, rating_store);
+    search
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/?<food>&<diet>&<exclude>&<date>&<dining_common>&<meal>")]
+fn food(
+    food_store: State<FoodStore>,
+    rating_store: State<RatingStore>,
+    food: String,
+    diet: Option<String>,
+    exclude: Option<String>,
+    date: Option<String>,
+    dining_common: Option<String>,
+    meal: Option<String>,
+) -> Result<Option<String>> {
+    Ok(
+        search_food(&food_store, food, diet, exclude, date, dining_common, meal)?
+            .map(|search| add_ratings(search, &rating_store).to_string()),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/json?<food>&<diet>&<exclude>&<date>&<dining_common>&<meal>")]
+fn food_json(
+    food_store: State<FoodStore>,
+    rating_store: State<RatingStore>,
+    food: String,
+    diet: Option<String>,
+    exclude: Option<String>,
+    date: Option<String>,
+    dining_common: Option<String>,
+    meal: Option<String>,
+) -> Result<Option<Json<FoodSearch>>> {
+    Ok(
+        search_food(&food_store, food, diet, exclude, date, dining_common, meal)?
+            .map(|search| Json(add_ratings(search, &rating_store))),
+    )
+}
+
+#[get("/changes?<food>&<since>&<dining_common>&<meal>")]
+fn changes(
+    food_store: State<FoodStore>,
+    food: String,
+    since: Option<u64>,
+    dining_common: Option<String>,
+    meal: Option<String>,
+) -> Option<Json<MenuChanges>> {
+    let place = get_place(&food_store, dining_common, meal)?;
+    Some(Json(food::get_changes(&food, &place, since, &food_store)))
+}
+
+#[get("/?<dish>&<date>")]
+fn nutrition(
+    food_store: State<FoodStore>,
+    dish: String,
+    date: Option<String>,
+) -> Result<Option<Json<Nutrition>>> {
+    match get_date(date) {
+        Some(date) => Ok(food::get_nutrition(&dish, date, &food_store)?.map(Json)),
+        None => Ok(None),
+    }
+}
+
+#[get("/<dining_common>/<meal>?<date>")]
+fn menu(
+    food_store: State<FoodStore>,
+    dining_common: String,
+    meal: String,
+    date: Option<String>,
+) -> Result<Option<String>> {
+    let dining_commons = food::dining_commons(&food_store);
+    match (
+        food::find_place(&dining_common, Some(&meal), &dining_commons),
+        get_date(date),
+    ) {
+        (Some((dining_common, Some(meal))), Some(date)) => Ok(Some(food::get_full_menu(
+            &dining_common,
+            meal,
+            date,
+            &food_store,
+        )?)),
+        _ => Ok(None),
+    }
+}
+
+// The dining common filter on the history routes is optional
+//  None if it was given but isn't a dining common
+fn get_dining_common(
+    food_store: &FoodStore,
+    dining_common: Option<String>,
+) -> Option<Option<DiningCommon>> {
+    match dining_common {
+        Some(dining_common) => {
+            food::dining_common_from_name(&dining_common, &food::dining_commons(food_store))
+                .map(Some)
+        }
+        None => Some(None),
+    }
+}
+
+#[get("/last?<food>&<dining_common>")]
+fn last_served(
+    food_store: State<FoodStore>,
+    food: String,
+    dining_common: Option<String>,
+) -> Option<String> {
+    match get_dining_common(&food_store, dining_common) {
+        Some(dining_common) => Some(history::last_served(
+            &food::history(&food_store),
+            &food,
+            dining_common.as_ref(),
+        )),
+        None => None,
+    }
+}
+
+#[get("/frequency?<food>&<dining_common>")]
+fn frequency(
+    food_store: State<FoodStore>,
+    food: String,
+    dining_common: Option<String>,
+) -> Option<String> {
+    match get_dining_common(&food_store, dining_common) {
+        Some(dining_common) => Some(history::frequency(
+            &food::history(&food_store),
+            &food,
+            dining_common.as_ref(),
+        )),
+        None => None,
+    }
+}
+
+#[get("/weekdays?<food>&<dining_common>")]
+fn weekdays(
+    food_store: State<FoodStore>,
+    food: String,
+    dining_common: Option<String>,
+) -> Option<String> {
+    match get_dining_common(&food_store, dining_common) {
+        Some(dining_common) => Some(history::weekdays(
+            &food::history(&food_store),
+            &food,
+            dining_common.as_ref(),
+        )),
+        None => None,
+    }
+}
+
+// Which locations are open at a time, or right now if no time is given
+#[get("/?<at>&<date>")]
+fn open(
+    food_store: State<FoodStore>,
+    hours_store: State<HoursStore>,
+    at: Option<String>,
+    date: Option<String>,
+) -> Option<String> {
+    let date = get_date(date)?;
+    let time = match at {
+        Some(at) => parse_time(&at)?,
+        None => get_datetime().time(),
+    };
+
+    Some(hours::open_at(
+        &hours_store,
+        &food::dining_commons(&food_store),
+        date.and_time(time)?,
+    ))
+}
+
+// When a location is open, or when it serves a meal
+#[get("/<dining_common>?<meal>&<date>")]
+fn opening_hours(
+    food_store: State<FoodStore>,
+    hours_store: State<HoursStore>,
+    dining_common: String,
+    meal: Option<String>,
+    date: Option<String>,
+) -> Option<String> {
+    let (dining_common, meal) = food::find_place(
+        &dining_common,
+        meal.as_ref(),
+        &food::dining_commons(&food_store),
+    )?;
+
+    Some(hours::hours_on(
+        &hours_store,
+        &dining_common,
+        meal,
+        get_date(date)?,
+    ))
+}
+
+// Rates the closest matching dish on today's menus, optionally with a review
+#[allow(clippy::too_many_arguments)]
+#[post("/?<dish>&<rating>&<user>&<review>&<dining_common>&<meal>")]
+fn rate(
+    food_store: State<FoodStore>,
+    rating_store: State<RatingStore>,
+    dish: String,
+    rating: u8,
+    user: String,
+    review: Option<String>,
+    dining_common: Option<String>,
+    meal: Option<String>,
+) -> Result<Option<String>> {
+    if rating < 1 || rating > ratings::MAX_STARS {
+        return Ok(None);
+    }
+    let place = match get_place(&food_store, dining_common, meal) {
+        Some(place) => place,
+        None => return Ok(None),
+    };
+
+    match food::find_dish(&dish, get_datetime().date(), &place, &food_store) {
+        Some((dining_common, dish)) => Ok(Some(ratings::rate(
+            &rating_store,
+            &dish.name,
+            &dining_common,
+            &user,
+            rating,
+            review.filter(|review| !review.trim().is_empty()),
+        )?)),
+        None => Ok(None),
+    }
+}
+
+#[get("/?<dish>")]
+fn reviews(rating_store: State<RatingStore>, dish: String) -> String {
+    ratings::reviews(&rating_store, &dish)
+}
+
+// Ranks the locations that are open at a time, or right now, by the favorites, diet and ratings
+//  Favorites are comma separated, like the diet tags
+#[allow(clippy::too_many_arguments)]
+#[get("/?<favorites>&<diet>&<exclude>&<at>&<date>")]
+fn recommend(
+    food_store: State<FoodStore>,
+    hours_store: State<HoursStore>,
+    rating_store: State<RatingStore>,
+    favorites: Option<String>,
+    diet: Option<String>,
+    exclude: Option<String>,
+    at: Option<String>,
+    date: Option<String>,
+) -> Result<Option<String>> {
+    let time = match at {
+        Some(at) => parse_time(&at),
+        None => Some(get_datetime().time()),
+    };
+    let time = match (get_date(date), time) {
+        (Some(date), Some(time)) => match date.and_time(time) {
+            Some(time) => time,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let favorites: Vec<String> = favorites
+        .unwrap_or_default()
+        .split(',')
+        .map(|favorite| favorite.trim().to_string())
+        .filter(|favorite| !favorite.is_empty())
+        .collect();
+
+    Ok(Some(recommend::recommend(
+        &favorites,
+        &DietFilter::from_params(diet, exclude),
+        time,
+        &hours_store,
+        &rating_store,
+        &food_store,
+    )?))
+}
+
+// Adds the closest matching dish on today's menus to the user's plate
+#[post("/?<user>&<dish>&<dining_common>&<meal>")]
+fn add_to_plate(
+    food_store: State<FoodStore>,
+    plate_store: State<PlateStore>,
+    user: String,
+    dish: String,
+    dining_common: Option<String>,
+    meal: Option<String>,
+) -> Option<String> {
+    let place = get_place(&food_store, dining_common, meal)?;
+    let (_, dish) = food::find_dish(&dish, get_datetime().date(), &place, &food_store)?;
+
+    Some(plates::add(&plate_store, &user, dish.nutrition()))
+}
+
+#[get("/?<user>")]
+fn plate(plate_store: State<PlateStore>, user: String) -> String {
+    plates::show(&plate_store, &user)
+}
+
+// Takes a dish off of the user's plate, or clears it if no dish is given
+#[delete("/?<user>&<dish>")]
+fn remove_from_plate(
+    plate_store: State<PlateStore>,
+    user: String,
+    dish: Option<String>,
+) -> Option<String> {
+    plates::remove(&plate_store, &user, dish.as_ref().map(|dish| dish.as_str()))
+}
+
+// Suggests dishes from a meal that fit a calorie limit or protein goal, counting the user's plate
+#[allow(clippy::too_many_arguments)]
+#[get("/suggest?<user>&<dining_common>&<meal>&<calories>&<protein>&<date>")]
+fn suggest_for_plate(
+    food_store: State<FoodStore>,
+    plate_store: State<PlateStore>,
+    user: String,
+    dining_common: String,
+    meal: String,
+    calories: Option<f64>,
+    protein: Option<f64>,
+    date: Option<String>,
+) -> Option<String> {
+    let (dining_common, meal) = match food::find_place(
+        &dining_common,
+        Some(&meal),
+        &food::dining_commons(&food_store),
+    )? {
+        (dining_common, Some(meal)) => (dining_common, meal),
+        _ => return None,
+    };
+    let target = match (calories, protein) {
+        (Some(calories), None) => Target::Calories(calories),
+        (None, Some(protein)) => Target::Protein(protein),
+        _ => return None,
+    };
+
+    plates::suggest(
+        &plate_store,
+        &user,
+        &dining_common,
+        meal,
+        get_date(date)?,
+        target,
+        &food_store,
+    )
 }
 
 fn main() {
+    let food_store = food::get_store();
+    refresh::start_refresher(food_store.clone(), refresh::RefreshSchedule::from_env());
+
+    let class_store = rooms::load_class_data();
+    let room_store = load_sections_map(&class_store);
+    let building_index = buildings::index_buildings(&room_store);
+
     rocket::ignite()
-        .manage(load_sections_map())
-        .manage(food::get_store().unwrap())
+        .manage(room_store)
+        .manage(building_index)
+        .manage(class_store)
+        .manage(food_store)
+        .manage(hours::load_hours())
+        .manage(ratings::load_ratings())
+        .manage(plates::new_store())
         .mount("/echo", routes![echo])
-        .mount("/room", routes![room])
-        .mount("/food", routes![food])
+        .mount(
+            "/room",
+            routes![room, room_schedule, room_status, free_rooms, buildings],
+        )
+        .mount("/class", routes![class])
+        .mount("/food", routes![food, food_json, changes])
+        .mount("/nutrition", routes![nutrition])
+        .mount("/menu", routes![menu])
+        .mount("/history", routes![last_served, frequency, weekdays])
+        .mount("/open", routes![open, opening_hours])
+        .mount("/ratings", routes![rate, reviews])
+        .mount("/recommend", routes![recommend])
+        .mount(
+            "/plate",
+            routes![add_to_plate, plate, remove_from_plate, suggest_for_plate],
+        )
         .launch();
 }
@@ -0,0 +1,210 @@
+// Matching food searches against dish names
+//
+// Both the search and the dish name are broken into words, with common abbreviations
+// expanded and plurals removed, so "mac n cheese" matches "Macaroni & Cheese".
+// Each searched word has to match a whole word in the dish name (so "tea" doesn't match
+// "Steak"), but small typos are tolerated.
+
+// Words that don't help tell dishes apart
+static STOP_WORDS: &[&str] = &["and", "n", "with", "w", "the", "of", "a", "in", "on"];
+
+// Abbreviations and alternate spellings, along with what they should be treated as
+static SYNONYMS: &[(&str, &str)] = &[
+    ("mac", "macaroni"),
+    ("parm", "parmesan"),
+    ("parmigiana", "parmesan"),
+    ("bbq", "barbecue"),
+    ("barbeque", "barbecue"),
+    ("veggie", "vegetable"),
+    ("veg", "vegetable"),
+    ("choc", "chocolate"),
+    ("mozz", "mozzarella"),
+    ("pb", "peanut butter"),
+    ("pbj", "peanut butter jelly"),
+    ("gf", "gluten free"),
+    ("donut", "doughnut"),
+    ("hotdog", "hot dog"),
+    ("chkn", "chicken"),
+];
+
+// Scores for how well a searched word matches a word in the dish name
+static EXACT_SCORE: f64 = 1.0;
+static PARTIAL_SCORE: f64 = 0.8;
+static TYPO_SCORE: f64 = 0.9;
+static TYPO_PENALTY: f64 = 0.15;
+
+// Bonus for matching the whole dish name, and penalty for each extra word in it,
+//  so that closer names are ranked first
+static EXACT_NAME_BONUS: f64 = 0.5;
+static EXTRA_WORD_PENALTY: f64 = 0.02;
+
+// Removes plural endings, so "fries" and "fry" or "potatoes" and "potato" are the same
+fn stem(word: &str) -> String {
+    if word.len() > 4 && word.ends_with("ies") {
+        format!("{}y", &word[..word.len() - 3])
+    } else if word.len() > 4
+        && (word.ends_with("oes")
+            || word.ends_with("ches")
+            || word.ends_with("shes")
+            || word.ends_with("xes")
+            || word.ends_with("sses"))
+    {
+        word[..word.len() - 2].to_string()
+    } else if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    }
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    let text: String = text
+        .to_lowercase()
+        .chars()
+        // "General Tso's" should become "tsos" rather than "tso" and "s"
+        .filter(|c| *c != '\'' && *c != '’')
+        .collect();
+
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !STOP_WORDS.contains(word))
+        .flat_map(
+            |word| match SYNONYMS.iter().find(|(synonym, _)| *synonym == word) {
+                Some((_, replacement)) => replacement.split(' ').collect(),
+                None => vec![word],
+            },
+        )
+        .map(stem)
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == b_char { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// How many typos to allow in a word, based on how long it is
+//  Short words get none, since one letter is the difference between "beef" and "beet"
+fn allowed_typos(word: &str) -> usize {
+    match word.chars().count() {
+        0..=4 => 0,
+        5..=7 => 1,
+        _ => 2,
+    }
+}
+
+fn word_score(searched: &str, word: &str) -> Option<f64> {
+    if searched == word {
+        return Some(EXACT_SCORE);
+    }
+
+    // "burger" should match "cheeseburger"
+    if searched.len() >= 4 && (word.starts_with(searched) || word.ends_with(searched)) {
+        return Some(PARTIAL_SCORE);
+    }
+
+    let distance = edit_distance(searched, word);
+    if distance <= allowed_typos(searched) {
+        Some(TYPO_SCORE - TYPO_PENALTY * distance as f64)
+    } else {
+        None
+    }
+}
+
+pub struct FoodMatcher {
+    tokens: Vec<String>,
+}
+
+impl FoodMatcher {
+    pub fn new(search: &str) -> FoodMatcher {
+        FoodMatcher {
+            tokens: tokenize(search),
+        }
+    }
+
+    // None if the search has nothing to match, such as when only filters were given
+    pub fn from_search(search: &str) -> Option<FoodMatcher> {
+        let matcher = FoodMatcher::new(search);
+        if matcher.tokens.is_empty() {
+            None
+        } else {
+            Some(matcher)
+        }
+    }
+
+    // None if the dish doesn't match, otherwise a score where higher is a closer match
+    //  An empty search doesn't match anything
+    pub fn score(&self, dish_name: &str) -> Option<f64> {
+        if self.tokens.is_empty() {
+            return None;
+        }
+
+        let dish_tokens = tokenize(dish_name);
+
+        let mut total = 0.0;
+        for searched in &self.tokens {
+            let best = dish_tokens
+                .iter()
+                .filter_map(|word| word_score(searched, word))
+                .fold(None, |best: Option<f64>, score| {
+                    Some(best.map_or(score, |best| best.max(score)))
+                })?;
+            total += best;
+        }
+
+        let mut score = total / self.tokens.len() as f64;
+        if dish_tokens == self.tokens {
+            score += EXACT_NAME_BONUS;
+        }
+        let extra_words = dish_tokens.len().saturating_sub(self.tokens.len());
+        score -= EXTRA_WORD_PENALTY * extra_words as f64;
+
+        Some(score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(search: &str, dish_name: &str) -> bool {
+        FoodMatcher::new(search).score(dish_name).is_some()
+    }
+
+    #[test]
+    fn short_words_need_exact_matches() {
+        assert!(!matches("beef", "Beet Salad"));
+        assert!(!matches("beet", "Roast Beef"));
+        assert!(!matches("rice", "Ice Cream"));
+        assert!(!matches("corn", "Apple Core"));
+    }
+
+    #[test]
+    fn longer_words_allow_typos() {
+        assert!(matches("chiken", "Chicken Tenders"));
+        assert!(matches("brocoli", "Steamed Broccoli"));
+    }
+
+    #[test]
+    fn expands_abbreviations_and_plurals() {
+        assert!(matches("mac n cheese", "Macaroni & Cheese"));
+        assert!(matches("potato", "Mashed Potatoes"));
+    }
+
+    #[test]
+    fn empty_search_matches_nothing() {
+        assert!(!matches("", "Pizza"));
+        assert!(FoodMatcher::from_search("the").is_none());
+    }
+}
@@ -0,0 +1,258 @@
+// Plates of dishes that users are putting together, to add up the macros of a meal
+//
+// Plates are only kept in memory, and are started over each day, since they're for planning a
+// single meal rather than tracking everything that's been eaten.
+
+use crate::food;
+use crate::food::{DiningCommon, FoodStore, Meal};
+use crate::matching::FoodMatcher;
+
+use chrono::offset::FixedOffset;
+use chrono::Date;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use umass_bot_common::datetime::{format_date, get_datetime};
+use umass_bot_common::food::Nutrition;
+
+// The most dishes to suggest at once
+static MAX_SUGGESTIONS: usize = 5;
+
+#[derive(Clone, Debug)]
+pub struct Plate {
+    // In the same format as the date query parameter
+    date: String,
+    dishes: Vec<Nutrition>,
+}
+
+// Keyed by user, like "discord:1234" or "telegram:1234"
+pub type PlateStore = Mutex<HashMap<String, Plate>>;
+
+// What a user wants the rest of their meal to add up to
+#[derive(Clone, Copy, Debug)]
+pub enum Target {
+    // At most this many calories
+    Calories(f64),
+    // At least this many grams of protein
+    Protein(f64),
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Totals {
+    calories: f64,
+    protein: f64,
+    carbs: f64,
+    fat: f64,
+}
+
+impl Totals {
+    // Dishes without an amount on their lightbox count as none of it
+    fn of<'a>(dishes: impl Iterator<Item = &'a Nutrition>) -> Totals {
+        dishes.fold(Totals::default(), |totals, dish| Totals {
+            calories: totals.calories + dish.calories.unwrap_or_default(),
+            protein: totals.protein + dish.protein.unwrap_or_default(),
+            carbs: totals.carbs + dish.total_carbs.unwrap_or_default(),
+            fat: totals.fat + dish.total_fat.unwrap_or_default(),
+        })
+    }
+
+    fn plus(&self, other: &Totals) -> Totals {
+        Totals {
+            calories: self.calories + other.calories,
+            protein: self.protein + other.protein,
+            carbs: self.carbs + other.carbs,
+            fat: self.fat + other.fat,
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} calories, {}g protein, {}g carbs, {}g fat",
+            self.calories.round(),
+            self.protein.round(),
+            self.carbs.round(),
+            self.fat.round()
+        )
+    }
+}
+
+pub fn new_store() -> PlateStore {
+    Mutex::new(HashMap::new())
+}
+
+// The user's plate for today, which is emptied if it was from an earlier day
+fn todays_plate<'a>(plates: &'a mut HashMap<String, Plate>, user: &str) -> &'a mut Plate {
+    let today = format_date(get_datetime().date());
+    let plate = plates.entry(user.to_string()).or_insert_with(|| Plate {
+        date: today.clone(),
+        dishes: vec![],
+    });
+    if plate.date != today {
+        plate.date = today;
+        plate.dishes.clear();
+    }
+    plate
+}
+
+fn describe_plate(plate: &Plate) -> String {
+    if plate.dishes.is_empty() {
+        return "Your plate is empty".to_string();
+    }
+
+    let dishes: Vec<&str> = plate.dishes.iter().map(|dish| dish.dish.as_str()).collect();
+    let mut lines = vec![
+        format!("Your plate: {}", dishes.join(", ")),
+        format!("Total: {}", Totals::of(plate.dishes.iter()).describe()),
+    ];
+
+    let missing: Vec<&str> = plate
+        .dishes
+        .iter()
+        .filter(|dish| dish.calories.is_none() || dish.protein.is_none())
+        .map(|dish| dish.dish.as_str())
+        .collect();
+    if !missing.is_empty() {
+        lines.push(format!(
+            "Not all of the nutrition facts are listed for {}",
+            missing.join(", ")
+        ));
+    }
+
+    lines.join("\n")
+}
+
+pub fn add(store: &PlateStore, user: &str, dish: Nutrition) -> String {
+    let mut plates = store.lock().unwrap();
+    let plate = todays_plate(&mut plates, user);
+
+    let added = format!("Added {}", dish.dish);
+    plate.dishes.push(dish);
+    format!("{}\n{}", added, describe_plate(plate))
+}
+
+pub fn show(store: &PlateStore, user: &str) -> String {
+    let mut plates = store.lock().unwrap();
+    describe_plate(todays_plate(&mut plates, user))
+}
+
+// Takes the closest matching dish off of the plate, or everything if no dish is given
+//  None if nothing on the plate matches
+pub fn remove(store: &PlateStore, user: &str, dish: Option<&str>) -> Option<String> {
+    let mut plates = store.lock().unwrap();
+    let plate = todays_plate(&mut plates, user);
+
+    let dish = match dish {
+        Some(dish) if dish.trim().is_empty() => return None,
+        Some(dish) => dish,
+        None => {
+            plate.dishes.clear();
+            return Some("Cleared your plate".to_string());
+        }
+    };
+
+    let matcher = FoodMatcher::new(dish);
+    let (index, _) = plate
+        .dishes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, on_plate)| Some((index, matcher.score(&on_plate.dish)?)))
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())?;
+
+    let removed = plate.dishes.remove(index);
+    Some(format!(
+        "Removed {}\n{}",
+        removed.dish,
+        describe_plate(plate)
+    ))
+}
+
+// Dishes from a meal that fit what's left of the target after what's already on the plate
+//  The ones with the most protein for their calories are picked first
+//  None if the dining common's menu for that meal couldn't be found
+pub fn suggest(
+    plate_store: &PlateStore,
+    user: &str,
+    dining_common: &DiningCommon,
+    meal: Meal,
+    date: Date<FixedOffset>,
+    target: Target,
+    store: &FoodStore,
+) -> Option<String> {
+    let totals = {
+        let mut plates = plate_store.lock().unwrap();
+        Totals::of(todays_plate(&mut plates, user).dishes.iter())
+    };
+    if let Target::Protein(target) = target {
+        if totals.protein >= target {
+            return Some(format!(
+                "Your plate already has {}g of protein",
+                totals.protein.round()
+            ));
+        }
+    }
+
+    let mut dishes: Vec<Nutrition> = food::dishes_served(dining_common, meal, date, store)?
+        .iter()
+        .map(|dish| dish.nutrition())
+        .filter(|dish| dish.calories.map_or(false, |calories| calories > 0.0))
+        .filter(|dish| dish.protein.is_some())
+        .collect();
+    let protein_per_calorie =
+        |dish: &Nutrition| dish.protein.unwrap_or_default() / dish.calories.unwrap_or(1.0);
+    dishes.sort_by(|a, b| {
+        protein_per_calorie(b)
+            .partial_cmp(&protein_per_calorie(a))
+            .unwrap()
+    });
+
+    let mut picked: Vec<&Nutrition> = vec![];
+    let mut added = Totals::default();
+    for dish in &dishes {
+        if picked.len() == MAX_SUGGESTIONS {
+            break;
+        }
+        let calories = dish.calories.unwrap_or_default();
+        match target {
+            Target::Calories(target) => {
+                if totals.calories + added.calories + calories > target {
+                    continue;
+                }
+            }
+            Target::Protein(target) => {
+                if totals.protein + added.protein >= target {
+                    break;
+                }
+            }
+        }
+        picked.push(dish);
+        added = Totals::of(picked.iter().cloned());
+    }
+
+    let goal = match target {
+        Target::Calories(target) => format!("{} calories", target),
+        Target::Protein(target) => format!("{}g of protein", target),
+    };
+    let place = format!("{} {}", dining_common, meal);
+    if picked.is_empty() {
+        return Some(format!(
+            "Nothing at {} fits {} with what's on your plate",
+            place, goal
+        ));
+    }
+
+    let mut lines = vec![format!("To get to {} at {}:", goal, place)];
+    lines.extend(picked.iter().map(|dish| {
+        format!(
+            "{} ({} calories, {}g protein)",
+            dish.dish,
+            dish.calories.unwrap_or_default().round(),
+            dish.protein.unwrap_or_default().round()
+        )
+    }));
+    lines.push(format!(
+        "Together with your plate: {}",
+        totals.plus(&added).describe()
+    ));
+    Some(lines.join("\n"))
+}
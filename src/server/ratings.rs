@@ -0,0 +1,194 @@
+// Ratings and reviews of dishes from the bots' users, saved in ratings.json
+//
+// Ratings are kept for each dish at each dining common, since the same dish can be better at one
+// than another, and each user has one rating per dish there that's replaced if they rate it again.
+
+use crate::matching::FoodMatcher;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::sync::Mutex;
+
+use umass_bot_common::datetime::{format_date, get_datetime};
+use umass_bot_common::error::*;
+use umass_bot_common::food::FoodSearch;
+
+static RATINGS_FILE: &str = "ratings.json";
+
+pub static MAX_STARS: u8 = 5;
+
+// How many reviews to show for each dish
+static REVIEWS_TO_SHOW: usize = 5;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Rating {
+    // The dish's name, as it's written on the menu
+    dish: String,
+    // The dining common's name
+    dining_common: String,
+    // Like "discord:1234" or "telegram:1234"
+    user: String,
+    stars: u8,
+    #[serde(default)]
+    review: Option<String>,
+    // When it was rated, in the same format as the date query parameter
+    date: String,
+}
+
+pub type RatingStore = Mutex<Vec<Rating>>;
+
+// No file just means nothing has been rated yet, but one that can't be read stops the server,
+//  since the next rating would overwrite it
+pub fn load_ratings() -> RatingStore {
+    let ratings = match fs::read_to_string(RATINGS_FILE) {
+        Ok(ratings) => serde_json::from_str(&ratings).expect("Invalid ratings json file"),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => vec![],
+        Err(err) => panic!("Couldn't read the ratings file: {}", err),
+    };
+
+    Mutex::new(ratings)
+}
+
+fn save_ratings(ratings: &[Rating]) -> Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(RATINGS_FILE)?
+        .write_all(serde_json::to_string(ratings)?.as_bytes())?;
+
+    Ok(())
+}
+
+// The average number of stars and how many ratings there are, if the dish has been rated there
+fn average<'a>(ratings: impl Iterator<Item = &'a Rating>) -> Option<(f64, usize)> {
+    let stars: Vec<u8> = ratings.map(|rating| rating.stars).collect();
+    if stars.is_empty() {
+        None
+    } else {
+        let total: u32 = stars.iter().map(|stars| u32::from(*stars)).sum();
+        Some((f64::from(total) / stars.len() as f64, stars.len()))
+    }
+}
+
+fn ratings_for<'a>(
+    ratings: &'a [Rating],
+    dish: &'a str,
+    dining_common: &'a str,
+) -> impl Iterator<Item = &'a Rating> {
+    ratings
+        .iter()
+        .filter(move |rating| rating.dish == dish && rating.dining_common == dining_common)
+}
+
+pub fn rate(
+    store: &RatingStore,
+    dish: &str,
+    dining_common: &str,
+    user: &str,
+    stars: u8,
+    review: Option<String>,
+) -> Result<String> {
+    let mut ratings = store.lock().unwrap();
+
+    let rating = Rating {
+        dish: dish.to_string(),
+        dining_common: dining_common.to_string(),
+        user: user.to_string(),
+        stars,
+        review,
+        date: format_date(get_datetime().date()),
+    };
+    match ratings.iter_mut().find(|existing| {
+        existing.dish == dish && existing.dining_common == dining_common && existing.user == user
+    }) {
+        Some(existing) => *existing = rating,
+        None => ratings.push(rating),
+    }
+    save_ratings(&ratings)?;
+
+    let (average, count) = average(ratings_for(&ratings, dish, dining_common)).unwrap_or_default();
+    Ok(format!(
+        "Rated {} at {} {}/{} (now {:.1}/{} from {} ratings)",
+        dish, dining_common, stars, MAX_STARS, average, MAX_STARS, count
+    ))
+}
+
+// Adds the average ratings to the dishes found by a search
+pub fn add_ratings(search: &mut FoodSearch, store: &RatingStore) {
+    let ratings = store.lock().unwrap();
+
+    for place in &mut search.found {
+        for dish in &mut place.dishes {
+            if let Some((average, count)) =
+                average(ratings_for(&ratings, &dish.name, &place.dining_common))
+            {
+                dish.average_rating = Some(average);
+                dish.ratings = count;
+            }
+        }
+    }
+}
+
+// The ratings and latest reviews of every rated dish matching the search, by dining common
+pub fn reviews(store: &RatingStore, dish: &str) -> String {
+    let ratings = store.lock().unwrap();
+    let matcher = FoodMatcher::new(dish);
+
+    let mut rated: Vec<(&str, &str)> = vec![];
+    for rating in ratings.iter() {
+        let key = (rating.dish.as_str(), rating.dining_common.as_str());
+        if !rated.contains(&key) && matcher.score(&rating.dish).is_some() {
+            rated.push(key);
+        }
+    }
+
+    if rated.is_empty() {
+        return format!("Nobody has rated {} yet", dish);
+    }
+
+    let mut summaries: Vec<(f64, String)> = rated
+        .into_iter()
+        .filter_map(|(rated_dish, dining_common)| {
+            let (average, count) = average(ratings_for(&ratings, rated_dish, dining_common))?;
+
+            let mut dish_reviews: Vec<&Rating> = ratings_for(&ratings, rated_dish, dining_common)
+                .filter(|rating| rating.review.is_some())
+                .collect();
+            dish_reviews.sort_by(|a, b| b.date.cmp(&a.date));
+
+            let mut lines = vec![format!(
+                "{} at {}: {:.1}/{} from {} ratings",
+                rated_dish, dining_common, average, MAX_STARS, count
+            )];
+            lines.extend(
+                dish_reviews
+                    .iter()
+                    .take(REVIEWS_TO_SHOW)
+                    .filter_map(|rating| {
+                        Some(format!(
+                            "  {}/{}: {}",
+                            rating.stars,
+                            MAX_STARS,
+                            rating.review.as_ref()?
+                        ))
+                    }),
+            );
+
+            Some((average, lines.join("\n")))
+        })
+        .collect();
+    // Best rated first, so arguments about which dining common does it best are settled quickly
+    summaries.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+
+    summaries
+        .into_iter()
+        .map(|(_, summary)| summary)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,160 @@
+// Ranks the dining commons that are open at a time by how well they suit someone, going by their
+// favorite foods, their diet and what other users have rated the dishes being served
+//
+// This only looks at the menus that have already been fetched, the same way a food search does.
+
+use crate::food::{DietFilter, FoodStore, PlaceFilter};
+use crate::hours::{describe_meal, HoursStore};
+use crate::matching::FoodMatcher;
+use crate::ratings::{RatingStore, MAX_STARS};
+use crate::{food, hours, ratings};
+
+use chrono::offset::FixedOffset;
+use chrono::DateTime;
+
+use umass_bot_common::datetime::format_time;
+use umass_bot_common::error::*;
+use umass_bot_common::food::DishMatch;
+
+// A favorite being served matters more than anything else
+static FAVORITE_WEIGHT: f64 = 10.0;
+// Having plenty to choose from that fits the diet breaks ties between places without favorites
+static DISH_WEIGHT: f64 = 0.2;
+
+struct Recommendation {
+    score: f64,
+    description: String,
+}
+
+fn best_rated(dishes: &[DishMatch]) -> Option<&DishMatch> {
+    dishes
+        .iter()
+        .filter(|dish| dish.average_rating.is_some())
+        .max_by(|a, b| a.average_rating.partial_cmp(&b.average_rating).unwrap())
+}
+
+// The open dining commons, best first, with why each one was picked
+pub fn recommend(
+    favorites: &[String],
+    filter: &DietFilter,
+    time: DateTime<FixedOffset>,
+    hours: &HoursStore,
+    rating_store: &RatingStore,
+    store: &FoodStore,
+) -> Result<String> {
+    let mut recommendations: Vec<Recommendation> = vec![];
+    let mut unavailable: Vec<String> = vec![];
+
+    for dining_common in food::dining_commons(store) {
+        let (day, period) = match hours::open_period(hours, &dining_common, time) {
+            Some(open) => open,
+            None => continue,
+        };
+        let place = PlaceFilter {
+            dining_common: Some(dining_common.clone()),
+            meal: period.meal,
+        };
+
+        // An empty search finds every dish that fits the diet
+        let mut search = food::get_food_on_menus("", day, filter, &place, store)?;
+        ratings::add_ratings(&mut search, rating_store);
+        unavailable.extend(search.unavailable.iter().cloned());
+        if !search.unavailable.is_empty() {
+            continue;
+        }
+
+        let dishes: Vec<DishMatch> = search
+            .found
+            .into_iter()
+            .flat_map(|found| found.dishes)
+            .collect();
+
+        let favorites_served: Vec<&str> = favorites
+            .iter()
+            .filter(|favorite| {
+                let matcher = FoodMatcher::new(favorite);
+                dishes
+                    .iter()
+                    .any(|dish| matcher.score(&dish.name).is_some())
+            })
+            .map(|favorite| favorite.as_str())
+            .collect();
+        let best = best_rated(&dishes);
+
+        let mut reasons: Vec<String> = vec![];
+        if !favorites_served.is_empty() {
+            reasons.push(format!(
+                "{} of your favorites ({})",
+                favorites_served.len(),
+                favorites_served.join(", ")
+            ));
+        }
+        if !filter.diets.is_empty() || !filter.excluded_allergens.is_empty() {
+            let diets: Vec<String> = filter
+                .diets
+                .iter()
+                .cloned()
+                .chain(
+                    filter
+                        .excluded_allergens
+                        .iter()
+                        .map(|allergen| format!("no {}", allergen)),
+                )
+                .collect();
+            reasons.push(format!("{} {} dishes", dishes.len(), diets.join(", ")));
+        }
+        if let Some(best) = best {
+            reasons.push(format!(
+                "{} rated {:.1}/{}",
+                best.name,
+                best.average_rating.unwrap_or_default(),
+                MAX_STARS
+            ));
+        }
+        if reasons.is_empty() {
+            reasons.push(format!("{} dishes", dishes.len()));
+        }
+
+        recommendations.push(Recommendation {
+            score: favorites_served.len() as f64 * FAVORITE_WEIGHT
+                + dishes.len() as f64 * DISH_WEIGHT
+                + best
+                    .and_then(|best| best.average_rating)
+                    .unwrap_or_default(),
+            description: format!(
+                "{}{} (until {}): {}",
+                dining_common,
+                describe_meal(period.meal),
+                format_time(period.close.time()),
+                reasons.join(", ")
+            ),
+        });
+    }
+
+    recommendations.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+
+    let when = format!(
+        "at {} on {}",
+        format_time(time.time()),
+        time.format("%A, %B %-d")
+    );
+    let mut lines: Vec<String> = if recommendations.is_empty() {
+        vec![format!("Nothing is open {}", when)]
+    } else {
+        let mut lines = vec![format!("Where to eat {}:", when)];
+        lines.extend(
+            recommendations
+                .into_iter()
+                .map(|recommendation| recommendation.description),
+        );
+        lines
+    };
+    if !unavailable.is_empty() {
+        lines.push(format!(
+            "Couldn't get the menus for {}",
+            unavailable.join(", ")
+        ));
+    }
+
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,97 @@
+// Keeps the cached menus up to date in the background, so requests never wait on the dining site
+//
+// Every day at the refresh hour the menus for the coming week are fetched. Today's menus are
+// also re-checked throughout the day, since the dining commons often change them after they're
+// first published.
+
+use crate::food;
+use crate::food::FoodStore;
+
+use std::env;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use umass_bot_common::datetime::{get_datetime, get_time_till};
+
+// How many days of menus to keep fetched, starting with today
+static DAYS_TO_PREFETCH: i64 = 7;
+
+// Re-checking more often than this would just be hammering the dining site
+static MIN_RECHECK_MINUTES: u64 = 5;
+
+// How long to wait before trying again when some cached menus couldn't be fetched
+static MINUTES_BETWEEN_RETRIES: u64 = 5;
+
+pub struct RefreshSchedule {
+    // The hour of the day to fetch the coming week's menus at
+    refresh_hour: u32,
+    // How often to re-check today's menus
+    recheck_interval: Duration,
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<T>().ok())
+        .unwrap_or(default)
+}
+
+impl RefreshSchedule {
+    // Configured with the MENU_REFRESH_HOUR and MENU_RECHECK_MINUTES environment variables
+    //  An hour that isn't 0 to 23 is ignored, and re-checks are at least a few minutes apart
+    pub fn from_env() -> RefreshSchedule {
+        let refresh_hour = match env_or("MENU_REFRESH_HOUR", 4) {
+            hour if hour < 24 => hour,
+            hour => {
+                println!("MENU_REFRESH_HOUR {} isn't an hour, using 4", hour);
+                4
+            }
+        };
+        let recheck_minutes = env_or("MENU_RECHECK_MINUTES", 60).max(MIN_RECHECK_MINUTES);
+
+        RefreshSchedule {
+            refresh_hour,
+            recheck_interval: Duration::from_secs(60 * recheck_minutes),
+        }
+    }
+}
+
+fn refresh_upcoming(store: &FoodStore) {
+    let today = get_datetime().date();
+    for days_ahead in 0..DAYS_TO_PREFETCH {
+        food::refresh_menus(store, today + chrono::Duration::days(days_ahead));
+    }
+    food::prune_store(store);
+}
+
+pub fn start_refresher(store: FoodStore, schedule: RefreshSchedule) {
+    thread::spawn(move || {
+        refresh_upcoming(&store);
+
+        loop {
+            let incomplete = food::incomplete_dates(&store);
+            let recheck_interval = if incomplete.is_empty() {
+                schedule.recheck_interval
+            } else {
+                Duration::from_secs(60 * MINUTES_BETWEEN_RETRIES)
+            };
+
+            let time_till_refresh = get_time_till(schedule.refresh_hour);
+            if time_till_refresh <= recheck_interval {
+                thread::sleep(time_till_refresh);
+                println!("Refreshing menus for the coming week");
+                refresh_upcoming(&store);
+            } else {
+                thread::sleep(recheck_interval);
+                println!("Re-checking today's menus");
+                let today = get_datetime().date();
+                food::refresh_menus(&store, today);
+                // Days that were fetched for a request, but not completely
+                for date in incomplete.into_iter().filter(|date| *date != today) {
+                    food::refresh_menus(&store, date);
+                }
+            }
+        }
+    });
+}
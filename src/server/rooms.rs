@@ -10,10 +10,20 @@ use std::io::Read;
 
 use std::fmt;
 
+use chrono::offset::FixedOffset;
+use chrono::DateTime;
+use chrono::Datelike;
 use chrono::NaiveTime;
 use chrono::Timelike;
+use chrono::Weekday;
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+use umass_bot_common::datetime::format_time;
+use umass_bot_common::query::normalize;
+
+use crate::buildings::{Building, BuildingIndex};
+
+// In order, so that comparing days compares when they are in the week
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 enum Day {
     Monday,
     Tuesday,
@@ -24,6 +34,51 @@ enum Day {
     Sunday,
 }
 
+static DAYS: [Day; 7] = [
+    Day::Monday,
+    Day::Tuesday,
+    Day::Wednesday,
+    Day::Thursday,
+    Day::Friday,
+    Day::Saturday,
+    Day::Sunday,
+];
+
+impl Day {
+    // Like "M" or "Th", for writing days together like "MWF" or "TuTh"
+    fn abbreviation(self) -> &'static str {
+        match self {
+            Day::Monday => "M",
+            Day::Tuesday => "Tu",
+            Day::Wednesday => "W",
+            Day::Thursday => "Th",
+            Day::Friday => "F",
+            Day::Saturday => "Sa",
+            Day::Sunday => "Su",
+        }
+    }
+}
+
+impl fmt::Display for Day {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{:?}", self)
+    }
+}
+
+impl Day {
+    fn from_weekday(weekday: Weekday) -> Day {
+        match weekday {
+            Weekday::Mon => Day::Monday,
+            Weekday::Tue => Day::Tuesday,
+            Weekday::Wed => Day::Wednesday,
+            Weekday::Thu => Day::Thursday,
+            Weekday::Fri => Day::Friday,
+            Weekday::Sat => Day::Saturday,
+            Weekday::Sun => Day::Sunday,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct Section {
     #[serde(deserialize_with = "deserialize_naive_time")]
@@ -35,6 +90,9 @@ pub struct Section {
     days: Vec<Day>,
     room: String,
     number: String,
+    // Filled in from the class when the sections are grouped by room
+    #[serde(default)]
+    class_name: String,
 }
 
 fn deserialize_naive_time<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
@@ -81,9 +139,53 @@ impl<'de> Visitor<'de> for NaiveTimeVisitor {
     }
 }
 
-// TODO: we want the start and end times to be a type that is easy to compare
-// Idea for it: make a new struct Time that contains a String for the time
-// and then implement Eq for it.
+static MINUTES_PER_DAY: i64 = 24 * 60;
+static MINUTES_PER_WEEK: i64 = 7 * 24 * 60;
+
+// A time during the week, which compares in order from Monday morning to Sunday night
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct WeekTime {
+    day: Day,
+    time: NaiveTime,
+}
+
+impl WeekTime {
+    fn at(time: DateTime<FixedOffset>) -> WeekTime {
+        WeekTime {
+            day: Day::from_weekday(time.weekday()),
+            time: time.time(),
+        }
+    }
+
+    // Minutes since the start of Monday
+    fn minutes(self) -> i64 {
+        self.day as i64 * MINUTES_PER_DAY + i64::from(self.time.num_seconds_from_midnight() / 60)
+    }
+
+    // Goes around to Monday if it's a week or more
+    fn from_minutes(minutes: i64) -> WeekTime {
+        let minutes = minutes.rem_euclid(MINUTES_PER_WEEK);
+        WeekTime {
+            day: DAYS[(minutes / MINUTES_PER_DAY) as usize],
+            time: NaiveTime::from_hms(
+                ((minutes % MINUTES_PER_DAY) / 60) as u32,
+                (minutes % 60) as u32,
+                0,
+            ),
+        }
+    }
+
+    // How long until another time, which is in the next week if it's earlier in the week
+    fn minutes_until(self, other: WeekTime) -> i64 {
+        (other.minutes() - self.minutes()).rem_euclid(MINUTES_PER_WEEK)
+    }
+}
+
+impl fmt::Display for WeekTime {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} {}", self.day, format_time(self.time))
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct Class {
@@ -91,8 +193,10 @@ pub struct Class {
     sections: Vec<Section>,
 }
 
+pub type ClassStore = Vec<Class>;
+
 // Get the json file from memory
-fn load_class_data() -> Vec<Class> {
+pub fn load_class_data() -> ClassStore {
     let mut spire_json = String::new();
     let _ = File::open("spire.json")
         .expect("No spire json file")
@@ -103,13 +207,12 @@ fn load_class_data() -> Vec<Class> {
 
 pub type RoomStore = HashMap<String, Vec<Section>>;
 
-pub fn load_sections_map() -> RoomStore {
-    let classes = load_class_data();
-
+pub fn load_sections_map(classes: &[Class]) -> RoomStore {
     let mut rooms_with_sections: HashMap<String, Vec<Section>> = HashMap::new();
 
     for class in classes {
-        for section in class.sections {
+        for mut section in class.sections.iter().cloned() {
+            section.class_name = class.name.clone();
             if rooms_with_sections.contains_key(&section.room) {
                 let room: &mut Vec<Section> = rooms_with_sections.get_mut(&section.room).unwrap();
                 room.push(section);
@@ -121,3 +224,339 @@ pub fn load_sections_map() -> RoomStore {
 
     rooms_with_sections
 }
+
+// SPIRE pads the course numbers with extra spaces, like "COMPSCI  187 Programming with..."
+fn collapse_spaces(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+impl Section {
+    // When each of the section's meetings starts and ends
+    fn meetings(&self) -> impl Iterator<Item = (WeekTime, WeekTime)> + '_ {
+        self.days.iter().map(move |day| {
+            (
+                WeekTime {
+                    day: *day,
+                    time: self.start_time,
+                },
+                WeekTime {
+                    day: *day,
+                    time: self.end_time,
+                },
+            )
+        })
+    }
+
+    // Whether the section meets at any point from the start time until the end time
+    fn meets_during(&self, start: WeekTime, end: WeekTime) -> bool {
+        self.meetings()
+            .any(|(meeting_start, meeting_end)| meeting_start < end && start < meeting_end)
+    }
+
+    fn class_name(&self) -> String {
+        collapse_spaces(&self.class_name)
+    }
+}
+
+// Like "3 hours 20 minutes"
+fn describe_minutes(minutes: i64) -> String {
+    let plural = |amount: i64, unit: &str| {
+        if amount == 1 {
+            format!("1 {}", unit)
+        } else {
+            format!("{} {}s", amount, unit)
+        }
+    };
+
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => plural(minutes, "minute"),
+        (hours, 0) => plural(hours, "hour"),
+        (hours, minutes) => format!("{} {}", plural(hours, "hour"), plural(minutes, "minute")),
+    }
+}
+
+// How many buildings to list free rooms in when no building is given, most free rooms first
+static BUILDINGS_TO_LIST: usize = 10;
+// How many free rooms to name in each of those buildings
+static ROOMS_PER_BUILDING: usize = 5;
+
+// Rooms where no section meets from a time until the given number of minutes after it, grouped by
+//  building. Without a building, only the buildings with the most free rooms are listed
+pub fn free_rooms(
+    rooms: &RoomStore,
+    building_index: &BuildingIndex,
+    time: DateTime<FixedOffset>,
+    building: Option<&Building>,
+    minutes: i64,
+) -> String {
+    let start = WeekTime::at(time);
+    // Free right now still means free for the next minute, so a class starting now counts
+    //  A window past the end of Sunday only has to be free until then
+    let end = WeekTime::from_minutes((start.minutes() + minutes.max(1)).min(MINUTES_PER_WEEK - 1));
+    let is_free = |room: &str| {
+        rooms.get(room).map_or(false, |sections| {
+            !sections
+                .iter()
+                .any(|section| section.meets_during(start, end))
+        })
+    };
+
+    let buildings: Vec<&Building> = match building {
+        Some(building) => vec![building],
+        None => building_index.buildings().iter().collect(),
+    };
+    // Rooms without a number, like "TBA" or "Off Campus", aren't in any building
+    let mut free: Vec<(&Building, Vec<&str>)> = buildings
+        .into_iter()
+        .map(|building| {
+            let free_rooms: Vec<&str> = building
+                .rooms
+                .iter()
+                .filter(|(_, room)| is_free(room))
+                .map(|(number, _)| number.as_str())
+                .collect();
+            (building, free_rooms)
+        })
+        .filter(|(_, free_rooms)| !free_rooms.is_empty())
+        .collect();
+
+    let mut when = format!(
+        "at {} on {}",
+        format_time(time.time()),
+        time.format("%A, %B %-d")
+    );
+    if minutes > 0 {
+        when = format!("{} for {}", when, describe_minutes(minutes));
+    }
+    if free.is_empty() {
+        return format!("No rooms are free {}", when);
+    }
+
+    if building.is_some() {
+        let lines: Vec<String> = free
+            .iter()
+            .map(|(building, free_rooms)| format!("{}: {}", building.name, free_rooms.join(", ")))
+            .collect();
+        return format!("Free {}: \n{}", when, lines.join("\n"));
+    }
+
+    free.sort_by(|(_, a), (_, b)| b.len().cmp(&a.len()));
+    let mut lines: Vec<String> = free
+        .iter()
+        .take(BUILDINGS_TO_LIST)
+        .map(|(building, free_rooms)| {
+            let mut shown = free_rooms
+                .iter()
+                .take(ROOMS_PER_BUILDING)
+                .cloned()
+                .collect::<Vec<&str>>()
+                .join(", ");
+            if free_rooms.len() > ROOMS_PER_BUILDING {
+                shown = format!(
+                    "{} and {} more",
+                    shown,
+                    free_rooms.len() - ROOMS_PER_BUILDING
+                );
+            }
+            format!("{}: {}", building.name, shown)
+        })
+        .collect();
+    if free.len() > BUILDINGS_TO_LIST {
+        lines.push(format!(
+            "and {} more buildings",
+            free.len() - BUILDINGS_TO_LIST
+        ));
+    }
+    lines.push("Add a building to see all of its free rooms".to_string());
+
+    format!("Free {}: \n{}", when, lines.join("\n"))
+}
+
+// Long enough for most rooms' schedules, but short enough to fit in a Discord message
+static MAX_SCHEDULE_LENGTH: usize = 1800;
+
+// Every section that meets in a room, by day and in order of when they start, or just on one day
+//  None if no classes meet in the room
+pub fn schedule(rooms: &RoomStore, room: &str, day: Option<Weekday>) -> Option<String> {
+    let sections = rooms.get(room)?;
+    let days: Vec<Day> = match day {
+        Some(day) => vec![Day::from_weekday(day)],
+        None => DAYS.to_vec(),
+    };
+
+    let mut lines = vec![format!("{}:", room)];
+    for day in days.iter() {
+        let mut on_day: Vec<&Section> = sections
+            .iter()
+            .filter(|section| section.days.contains(day))
+            .collect();
+        if on_day.is_empty() {
+            continue;
+        }
+        on_day.sort_by_key(|section| (section.start_time, section.end_time));
+
+        lines.push(day.to_string());
+        lines.extend(on_day.iter().map(|section| {
+            format!(
+                "  {} to {}: {} ({})",
+                format_time(section.start_time),
+                format_time(section.end_time),
+                section.class_name(),
+                section.number
+            )
+        }));
+    }
+
+    if lines.len() == 1 {
+        lines.push("No classes meet then".to_string());
+    }
+
+    // Some rooms, like auditoriums, have more sections than fit in a message
+    let mut schedule = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        if schedule.len() + line.len() > MAX_SCHEDULE_LENGTH {
+            let more = lines[index..]
+                .iter()
+                .filter(|line| line.starts_with("  "))
+                .count();
+            schedule.push_str(&match day {
+                Some(_) => format!("and {} more", more),
+                None => format!(
+                    "and {} more, ask for one day like \"{} on tuesday\" to see the rest",
+                    more, room
+                ),
+            });
+            break;
+        }
+        schedule.push_str(line);
+        schedule.push('\n');
+    }
+
+    Some(schedule.trim_end().to_string())
+}
+
+// Whether a room has a class at a time, and until when it's taken or free
+//  None if no classes meet in the room
+pub fn room_status(rooms: &RoomStore, room: &str, time: DateTime<FixedOffset>) -> Option<String> {
+    let sections = rooms.get(room)?;
+    let now = WeekTime::at(time);
+
+    let current = sections.iter().find_map(|section| {
+        section
+            .meetings()
+            .find(|(start, end)| *start <= now && now < *end)
+            .map(|(_, end)| (section, end))
+    });
+    if let Some((section, end)) = current {
+        return Some(format!(
+            "{} has {} until {} ({} from now)",
+            room,
+            section.class_name(),
+            format_time(end.time),
+            describe_minutes(now.minutes_until(end))
+        ));
+    }
+
+    // The next class could be next week, if nothing else meets there this week
+    let (section, start) = sections
+        .iter()
+        .flat_map(|section| section.meetings().map(move |(start, _)| (section, start)))
+        .min_by_key(|(_, start)| now.minutes_until(*start))?;
+    Some(format!(
+        "{} is free until {} ({} from now), when {} starts",
+        room,
+        start,
+        describe_minutes(now.minutes_until(start)),
+        section.class_name()
+    ))
+}
+
+// How many courses to list the sections of, before only listing the courses' names
+static COURSES_WITH_SECTIONS: usize = 3;
+// How many courses' names to list when there are too many to show the sections of
+static COURSES_TO_LIST: usize = 20;
+
+impl Class {
+    fn name(&self) -> String {
+        collapse_spaces(&self.name)
+    }
+
+    // Whether the search is the class's subject, subject and number, or part of its title
+    //  A blank search doesn't match anything, rather than the whole catalog
+    fn matches(&self, search: &str) -> bool {
+        let normalized = normalize(search);
+        if normalized.is_empty() {
+            return false;
+        }
+
+        let words: Vec<&str> = self.name.split_whitespace().collect();
+        let subject = normalize(words.get(0).unwrap_or(&""));
+        let course = format!("{}{}", subject, normalize(words.get(1).unwrap_or(&"")));
+
+        normalized == subject
+            || normalized == course
+            || self
+                .name()
+                .to_lowercase()
+                .contains(&search.trim().to_lowercase())
+    }
+}
+
+fn describe_section(section: &Section) -> String {
+    let days: String = section.days.iter().map(|day| day.abbreviation()).collect();
+    format!(
+        "  {}: {} {} to {} in {}",
+        section.number,
+        days,
+        format_time(section.start_time),
+        format_time(section.end_time),
+        section.room
+    )
+}
+
+// The courses matching a search, like "COMPSCI 187" or "data structures", with their sections'
+//  meeting times and rooms. None if nothing matches
+pub fn search_classes(classes: &ClassStore, search: &str) -> Option<String> {
+    // SPIRE lists a course once for each group of sections, so they're put back together
+    let mut courses: Vec<(String, Vec<&Section>)> = vec![];
+    for class in classes.iter().filter(|class| class.matches(search)) {
+        let name = class.name();
+        match courses.iter_mut().find(|(course, _)| *course == name) {
+            Some((_, sections)) => sections.extend(class.sections.iter()),
+            None => courses.push((name, class.sections.iter().collect())),
+        }
+    }
+    if courses.is_empty() {
+        return None;
+    }
+
+    if courses.len() > COURSES_WITH_SECTIONS {
+        let mut lines = vec![format!(
+            "{} courses match {}, search for one to see its sections:",
+            courses.len(),
+            search.trim()
+        )];
+        lines.extend(
+            courses
+                .iter()
+                .take(COURSES_TO_LIST)
+                .map(|(course, _)| course.clone()),
+        );
+        if courses.len() > COURSES_TO_LIST {
+            lines.push(format!("and {} more", courses.len() - COURSES_TO_LIST));
+        }
+        return Some(lines.join("\n"));
+    }
+
+    Some(
+        courses
+            .iter()
+            .map(|(course, sections)| {
+                let mut lines = vec![format!("{}:", course)];
+                lines.extend(sections.iter().map(|section| describe_section(section)));
+                lines.join("\n")
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+    )
+}
@@ -20,13 +20,22 @@ use std::ops::DerefMut;
 
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
 // For telegram
+use futures::sync::mpsc;
+use futures::Future;
 use futures::Stream;
 use telegram_bot::*;
 
 // For requests to server
 use reqwest::Url;
+use umass_bot_common::datetime::get_time_till_scheduled;
+use umass_bot_common::food::{Digest, FoodSearch, MenuChanges, Nutrition};
+use umass_bot_common::query::{
+    date_params, free_room_params, open_params, recommend_params, room_schedule_params,
+    room_status_params, split_date, split_rating, target_params, FoodQuery,
+};
 
 use tokio_core::reactor::Core;
 
@@ -52,31 +61,120 @@ impl TelegramChannel {
     }
 }
 
+// Messages sent from other threads, since the api can only be used on the thread running the core
+type Outbox = mpsc::UnboundedSender<(TelegramChannel, String)>;
+
+fn send_from_thread(outbox: &Outbox, channel: &TelegramChannel, message: &str) {
+    if outbox
+        .unbounded_send((channel.clone(), message.to_string()))
+        .is_err()
+    {
+        println!("Couldn't send {} to {:?}", message, channel);
+    }
+}
+
+// How often to ask the server whether menus changed since the last check
+static MINUTES_BETWEEN_CHANGE_CHECKS: u64 = 15;
+
 type ResponseCode = reqwest::StatusCode;
 
 fn send_get(url: String) -> (String, ResponseCode) {
-    let url = url.replace(" ", "%20");
+    send_request(reqwest::Method::GET, url)
+}
 
-    let mutex = Arc::new(Mutex::new(None));
-    let mutex_c = mutex.clone();
+fn send_post(url: String) -> (String, ResponseCode) {
+    send_request(reqwest::Method::POST, url)
+}
+
+fn send_delete(url: String) -> (String, ResponseCode) {
+    send_request(reqwest::Method::DELETE, url)
+}
+
+// Replies with a message about the server instead of panicking when it can't be reached
+fn send_request(method: reqwest::Method, url: String) -> (String, ResponseCode) {
+    try_send_request(method, url).unwrap_or_else(|| {
+        (
+            "Couldn't reach the server".to_string(),
+            ResponseCode::BAD_GATEWAY,
+        )
+    })
+}
 
-    let t = thread::spawn(move || {
+// For the background threads, which skip a check when the server can't be reached
+fn try_send_get(url: String) -> Option<(String, ResponseCode)> {
+    try_send_request(reqwest::Method::GET, url)
+}
+
+// None if the server couldn't be reached
+fn try_send_request(method: reqwest::Method, url: String) -> Option<(String, ResponseCode)> {
+    let url = url.replace(" ", "%20");
+
+    let t = thread::spawn(move || -> reqwest::Result<(String, ResponseCode)> {
         let client = reqwest::Client::new();
-        let mut response = client
-            .get::<Url>(url.parse::<Url>().unwrap())
-            .send()
-            .unwrap();
-        *mutex_c.lock().unwrap() = Some((response.text().unwrap(), response.status()));
+        let mut response = client.request(method, &url).send()?;
+        Ok((response.text()?, response.status()))
     });
 
-    t.join().unwrap();
-    let unlocked_mutex = mutex.lock().unwrap();
-    let (body, status) = unlocked_mutex.as_ref().unwrap();
-    (body.to_string(), *status)
+    match t.join() {
+        Ok(Ok(response)) => Some(response),
+        Ok(Err(err)) => {
+            println!("Request to the server failed: {}", err);
+            None
+        }
+        Err(_) => {
+            println!("Request to the server panicked");
+            None
+        }
+    }
+}
+
+// None if the server couldn't be asked, and Some(None) when it doesn't know the dining hall, meal
+//  or date that was searched for
+fn search_food(food: &str) -> Option<Option<FoodSearch>> {
+    let url = Url::parse_with_params(
+        "http://localhost:8000/food/json",
+        &FoodQuery::parse(food).to_params(),
+    )
+    .unwrap();
+    // Used by the digest thread, so a server that's down is just a failed check
+    let (body, status_code) = try_send_get(url.to_string())?;
+
+    match status_code {
+        ResponseCode::NOT_FOUND => Some(None),
+        ResponseCode::OK => serde_json::from_str(&body).ok().map(Some),
+        _ => None,
+    }
 }
 
 fn check_food(food: String) -> String {
-    send_get(format!("http://localhost:8000/food?food={}", food)).0
+    match search_food(&food) {
+        Some(Some(search)) => search.to_string(),
+        Some(None) => format!("Couldn't find that dining hall, meal or date for {}", food),
+        None => format!("Couldn't check for {}", food),
+    }
+}
+
+// Asks the server about past menus, where route is "last", "frequency" or "weekdays"
+//  The input can be narrowed to one dining common, like "sushi @hamp"
+fn check_history(route: &str, input: &str) -> String {
+    let query = FoodQuery::parse(input);
+    let mut params = vec![("food", query.food.clone())];
+    params.extend(
+        query
+            .place_params()
+            .into_iter()
+            .filter(|(name, _)| *name == "dining_common"),
+    );
+
+    let url = Url::parse_with_params(&format!("http://localhost:8000/history/{}", route), &params)
+        .unwrap();
+    let (body, status_code) = send_get(url.to_string());
+
+    if status_code == 404 {
+        return "Couldn't find that dining hall".to_string();
+    }
+
+    body
 }
 
 // Get the telegram token file from memory
@@ -137,6 +235,54 @@ fn handle_message(
         let response = check_food(item.to_string());
 
         channel.send_message(&response, &telegram_api);
+    } else if content.starts_with("/listmenu ") {
+        let (input, date) = split_date(&content[10..]);
+        let mut words = input.splitn(2, ' ');
+        let dining_common: &str = words.next().unwrap_or("");
+        let meal: &str = words.next().unwrap_or("");
+
+        let url = Url::parse_with_params(
+            &format!("http://localhost:8000/menu/{}/{}", dining_common, meal),
+            &date_params(date),
+        )
+        .unwrap();
+        let (body, status_code) = send_get(url.to_string());
+
+        if status_code == 200 {
+            channel.send_message(&body, &telegram_api);
+        } else {
+            channel.send_message(
+                &format!("Couldn't find the {} menu for {}", meal, dining_common),
+                &telegram_api,
+            );
+        }
+    } else if content.starts_with("/nutrition ") {
+        let (dish, date) = split_date(&content[11..]);
+
+        let mut params = date_params(date);
+        params.push(("dish", dish.to_string()));
+        let url = Url::parse_with_params("http://localhost:8000/nutrition", &params).unwrap();
+        let (body, status_code) = send_get(url.to_string());
+
+        let response = if status_code != 200 {
+            format!("Couldn't find {} on the menus", dish)
+        } else {
+            match serde_json::from_str::<Nutrition>(&body) {
+                Ok(nutrition) => nutrition.to_string(),
+                Err(err) => {
+                    println!("Couldn't read the nutrition facts for {}: {}", dish, err);
+                    format!("Couldn't get the nutrition facts for {}", dish)
+                }
+            }
+        };
+
+        channel.send_message(&response, &telegram_api);
+    } else if content.starts_with("/lastserved ") {
+        channel.send_message(&check_history("last", &content[12..]), &telegram_api);
+    } else if content.starts_with("/howoften ") {
+        channel.send_message(&check_history("frequency", &content[10..]), &telegram_api);
+    } else if content.starts_with("/whichday ") {
+        channel.send_message(&check_history("weekdays", &content[10..]), &telegram_api);
     } else if content.starts_with("/echo ") {
         let input: String = content[6..].to_string();
 
@@ -171,31 +317,346 @@ fn handle_message(
         } else {
             channel.send_message(&format!("Couldn't find {}", item), &telegram_api);
         }
+    } else if content == "/open" || content.starts_with("/open ") {
+        let input: &str = &content[5..];
+
+        let url =
+            Url::parse_with_params("http://localhost:8000/open/", &open_params(input)).unwrap();
+        let (body, status_code) = send_get(url.to_string());
+
+        if status_code == 200 {
+            channel.send_message(&body, &telegram_api);
+        } else {
+            channel.send_message(
+                &format!("Couldn't understand the time {}", input.trim()),
+                &telegram_api,
+            );
+        }
+    } else if content.starts_with("/hours ") {
+        let (input, date) = split_date(&content[7..]);
+        let mut words = input.splitn(2, ' ');
+        let dining_common: &str = words.next().unwrap_or("");
+        let meal: &str = words.next().unwrap_or("");
+
+        let mut params = date_params(date);
+        if !meal.is_empty() {
+            params.push(("meal", meal.to_string()));
+        }
+
+        let url = Url::parse_with_params(
+            &format!("http://localhost:8000/open/{}", dining_common),
+            &params,
+        )
+        .unwrap();
+        let (body, status_code) = send_get(url.to_string());
+
+        if status_code == 200 {
+            channel.send_message(&body, &telegram_api);
+        } else {
+            channel.send_message(
+                &format!("Couldn't find the hours for {} {}", dining_common, meal),
+                &telegram_api,
+            );
+        }
+    } else if content.starts_with("/rate ") {
+        match split_rating(&content[6..]) {
+            Some((dish, stars, review)) => {
+                let query = FoodQuery::parse(&dish);
+                let mut params = vec![
+                    ("dish", query.food.clone()),
+                    ("rating", stars.to_string()),
+                    ("user", format!("telegram:{}", author.id)),
+                ];
+                params.extend(query.place_params());
+                if let Some(review) = review {
+                    params.push(("review", review));
+                }
+
+                let url =
+                    Url::parse_with_params("http://localhost:8000/ratings/", &params).unwrap();
+                let (body, status_code) = send_post(url.to_string());
+
+                if status_code == 200 {
+                    channel.send_message(&body, &telegram_api);
+                } else {
+                    channel.send_message(
+                        &format!("Couldn't find {} on today's menus", query.food),
+                        &telegram_api,
+                    );
+                }
+            }
+            None => channel.send_message(
+                "Rate a dish from 1 to 5 like /rate stir fry 4, optionally followed by a review",
+                &telegram_api,
+            ),
+        }
+    } else if content.starts_with("/reviews ") {
+        let dish: &str = &content[9..];
+
+        let url =
+            Url::parse_with_params("http://localhost:8000/ratings/", &[("dish", dish)]).unwrap();
+        let (body, _) = send_get(url.to_string());
+
+        channel.send_message(&body, &telegram_api);
+    } else if content == "/recommend" || content.starts_with("/recommend ") {
+        let input: &str = &content[10..];
+
+        // The channel's registered foods are taken as its favorites
+        let favorites: Vec<String> = listeners
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(listener, _)| *listener == channel)
+            .map(|(_, food)| food.clone())
+            .collect();
+
+        let url = Url::parse_with_params(
+            "http://localhost:8000/recommend/",
+            &recommend_params(input, &favorites),
+        )
+        .unwrap();
+        let (body, status_code) = send_get(url.to_string());
+
+        if status_code == 200 {
+            channel.send_message(&body, &telegram_api);
+        } else {
+            channel.send_message(
+                &format!("Couldn't understand the time {}", input.trim()),
+                &telegram_api,
+            );
+        }
+    } else if content == "/plate" || content.starts_with("/plate ") {
+        let user = format!("telegram:{}", author.id);
+        let mut words = content[6..].trim().splitn(2, ' ');
+        let action: &str = words.next().unwrap_or("");
+        let input: &str = words.next().unwrap_or("").trim();
+
+        match action {
+            "add" => {
+                let query = FoodQuery::parse(input);
+                let mut params = vec![("user", user), ("dish", query.food.clone())];
+                params.extend(query.place_params());
+
+                let url = Url::parse_with_params("http://localhost:8000/plate/", &params).unwrap();
+                let (body, status_code) = send_post(url.to_string());
+
+                if status_code == 200 {
+                    channel.send_message(&body, &telegram_api);
+                } else {
+                    channel.send_message(
+                        &format!("Couldn't find {} on today's menus", query.food),
+                        &telegram_api,
+                    );
+                }
+            }
+            "remove" | "clear" => {
+                let mut params = vec![("user", user)];
+                if action == "remove" {
+                    params.push(("dish", input.to_string()));
+                }
+
+                let url = Url::parse_with_params("http://localhost:8000/plate/", &params).unwrap();
+                let (body, status_code) = send_delete(url.to_string());
+
+                if status_code == 200 {
+                    channel.send_message(&body, &telegram_api);
+                } else {
+                    channel.send_message(
+                        &format!("Couldn't find {} on your plate", input),
+                        &telegram_api,
+                    );
+                }
+            }
+            "suggest" => match target_params(input) {
+                Some(mut params) => {
+                    params.push(("user", user));
+
+                    let url =
+                        Url::parse_with_params("http://localhost:8000/plate/suggest", &params)
+                            .unwrap();
+                    let (body, status_code) = send_get(url.to_string());
+
+                    if status_code == 200 {
+                        channel.send_message(&body, &telegram_api);
+                    } else {
+                        channel.send_message(
+                            &format!("Couldn't find the menu for {}", input),
+                            &telegram_api,
+                        );
+                    }
+                }
+                None => channel.send_message("Suggest dishes for a target like /plate suggest berk dinner 600 calories or /plate suggest worcester lunch 30g protein", &telegram_api),
+            },
+            _ => {
+                let url =
+                    Url::parse_with_params("http://localhost:8000/plate/", &[("user", user)])
+                        .unwrap();
+                let (body, _) = send_get(url.to_string());
+
+                channel.send_message(&body, &telegram_api);
+            }
+        }
     } else if content == "/help" {
         channel.send_message(
             "/menu [food name] => tells you where that food is being served today",
             &telegram_api,
         );
 
-        channel.send_message("/register [food name] => schedules it to tell you each day where that food is being served that day", &telegram_api);
+        channel.send_message("Add +[diet] (like +vegan or +halal) to only show dishes with that diet tag, or -[allergen] (like -peanuts) to hide dishes containing that allergen", &telegram_api);
+
+        channel.send_message("Add @[dining common] [meal] (like @berk dinner, @worcester or @late night) to the end of /menu or /register to only check that dining common or meal", &telegram_api);
+
+        channel.send_message("Add a day (like tomorrow or friday) to /menu, /listmenu or /nutrition to check that day's menus instead of today's", &telegram_api);
+
+        channel.send_message(
+            "/listmenu [dining common] [meal] => shows the full menu for that dining common and meal today",
+            &telegram_api,
+        );
+
+        channel.send_message(
+            "/nutrition [dish name] => tells you the calories, macros and ingredients of a dish on today's menus",
+            &telegram_api,
+        );
+
+        channel.send_message(
+            "/lastserved [food name] @[dining common] => tells you the last time that food was served, anywhere or at one dining common",
+            &telegram_api,
+        );
+
+        channel.send_message(
+            "/howoften [food name] @[dining common] => tells you how many days each dining common (or just one) has served that food",
+            &telegram_api,
+        );
+
+        channel.send_message(
+            "/whichday [food name] @[dining common] => tells you which days of the week each dining common (or just one) usually serves that food",
+            &telegram_api,
+        );
+
+        channel.send_message(
+            "/open [time] => tells you what's open at that time (like 9pm or noon tomorrow), or right now",
+            &telegram_api,
+        );
+
+        channel.send_message(
+            "/hours [dining common] [meal] => tells you when that dining common (or just that meal) is open today",
+            &telegram_api,
+        );
+
+        channel.send_message(
+            "/rate [dish name] [1-5] [review] => rates a dish on today's menus, with an optional review",
+            &telegram_api,
+        );
+
+        channel.send_message(
+            "/reviews [dish name] => shows how each dining common's version of a dish has been rated",
+            &telegram_api,
+        );
+
+        channel.send_message("/plate add [dish name], /plate remove [dish name] or /plate clear => builds a plate from today's menus and adds up its calories, protein, carbs and fat (just /plate shows it)", &telegram_api);
+
+        channel.send_message("/plate suggest [dining common] [meal] [amount] calories or protein => suggests dishes that fit the rest of a calorie limit or protein goal", &telegram_api);
+
+        channel.send_message("/recommend [time] => ranks what's open at that time (or right now) by your registered foods, ratings and any +diet or -allergen you add", &telegram_api);
+
+        channel.send_message("/register [food name] => adds it to the digest sent each morning of where your registered foods are being served that day", &telegram_api);
 
         channel.send_message(
             "/deregister [food name] => removes a registered food",
             &telegram_api,
         );
+
+        channel.send_message(
+            "/room [room] [on day] => shows the classes that meet in a room each day of the week, or just on one day",
+            &telegram_api,
+        );
+
+        channel.send_message("/room [room] free? [at time] => tells you whether a room is free, and until when (like /room LGRT 141 free? at 2pm)", &telegram_api);
+
+        channel.send_message("/class [course] => lists a course's sections with when and where they meet, by subject and number (like /class COMPSCI 187) or part of the title", &telegram_api);
+
+        channel.send_message("/buildings [building] => lists the buildings and their abbreviations, or the rooms in one building", &telegram_api);
+
+        channel.send_message("/freerooms [building] at [time] for [minutes or hours] => lists the rooms without a class then (like /freerooms lgrt at 2pm for 2 hours), or right now", &telegram_api);
     } else if content.starts_with("/room ") {
         let room: String = content[6..].to_string();
 
-        let (_, status_code) = send_get(format!("http://localhost:8000/room/?room={}", room));
+        // Like "LGRT 141 free?" asks whether it's free, rather than for its schedule
+        let url = match room_status_params(&room) {
+            Some(params) => {
+                Url::parse_with_params("http://localhost:8000/room/status", &params).unwrap()
+            }
+            None => Url::parse_with_params(
+                "http://localhost:8000/room/schedule",
+                &room_schedule_params(&room),
+            )
+            .unwrap(),
+        };
+        let (body, status_code) = send_get(url.to_string());
 
         if status_code == 200 {
-            channel.send_message("Rooms found", &telegram_api);
+            channel.send_message(&body, &telegram_api);
         } else {
             channel.send_message("No rooms found", &telegram_api);
         }
+    } else if content == "/freerooms" || content.starts_with("/freerooms ") {
+        let input: &str = &content[10..];
+
+        let url =
+            Url::parse_with_params("http://localhost:8000/room/free", &free_room_params(input))
+                .unwrap();
+        let (body, status_code) = send_get(url.to_string());
+
+        if status_code == 200 {
+            channel.send_message(&body, &telegram_api);
+        } else {
+            channel.send_message(
+                &format!(
+                    "Couldn't find the building or understand the time in {}",
+                    input.trim()
+                ),
+                &telegram_api,
+            );
+        }
+    } else if content == "/buildings" || content.starts_with("/buildings ") {
+        let building: &str = content[10..].trim();
+
+        let mut params = vec![];
+        if !building.is_empty() {
+            params.push(("building", building));
+        }
+
+        let url = Url::parse_with_params("http://localhost:8000/room/buildings", &params).unwrap();
+        let (body, status_code) = send_get(url.to_string());
+
+        if status_code == 200 {
+            channel.send_message(&body, &telegram_api);
+        } else {
+            channel.send_message(
+                &format!("Couldn't find the building {}", building),
+                &telegram_api,
+            );
+        }
+    } else if content.starts_with("/class ") {
+        let search: &str = &content[7..];
+
+        let url =
+            Url::parse_with_params("http://localhost:8000/class/", &[("search", search)]).unwrap();
+        let (body, status_code) = send_get(url.to_string());
+
+        if status_code == 200 {
+            channel.send_message(&body, &telegram_api);
+        } else {
+            channel.send_message(
+                &format!("Couldn't find any classes matching {}", search),
+                &telegram_api,
+            );
+        }
     } else if content == "/run" {
         channel.send_message("Checking for preregistered foods", &telegram_api);
-        check_for_foods(&listeners, &telegram_api);
+        check_for_foods(&listeners, &|channel, message| {
+            channel.send_message(message, &telegram_api)
+        });
     } else if content.starts_with("/quit") && author.is_owner {
         channel.send_message("UMass Bot Quitting", &telegram_api);
         std::process::exit(0);
@@ -230,18 +691,92 @@ fn save_listeners(pairs: &[(TelegramChannel, String)]) {
         .write_all(listeners_string.as_bytes());
 }
 
-fn check_for_foods(listeners: &Arc<Mutex<Vec<(TelegramChannel, String)>>>, telegram_api: &Api) {
+// Sends each channel one message with all of the foods it registered for
+fn check_for_foods(
+    listeners: &Arc<Mutex<Vec<(TelegramChannel, String)>>>,
+    send: &dyn Fn(&TelegramChannel, &str),
+) {
+    let mut digests: Vec<(TelegramChannel, Digest)> = vec![];
+    for (channel, food) in listeners.lock().unwrap().to_vec() {
+        println!("Checking on {:?} for {}", channel, food);
+        let index = match digests
+            .iter()
+            .position(|(digest_channel, _)| *digest_channel == channel)
+        {
+            Some(index) => index,
+            None => {
+                digests.push((channel, Digest::default()));
+                digests.len() - 1
+            }
+        };
+
+        match search_food(&food) {
+            Some(Some(search)) => digests[index].1.add(&search),
+            _ => digests[index].1.add_failed(&food),
+        }
+    }
+
+    for (channel, digest) in digests {
+        send(&channel, &digest.to_string());
+    }
+}
+
+fn get_changes(query: &FoodQuery, since: Option<u64>) -> Option<MenuChanges> {
+    let mut params = vec![("food", query.food.clone())];
+    params.extend(query.place_params());
+    if let Some(since) = since {
+        params.push(("since", since.to_string()));
+    }
+
+    let url = Url::parse_with_params("http://localhost:8000/food/changes", &params).unwrap();
+    let (body, status_code) = try_send_get(url.to_string())?;
+
+    if status_code != 200 {
+        return None;
+    }
+
+    serde_json::from_str(&body).ok()
+}
+
+// Tells listeners about menu changes since the last check, and returns the id to check from next
+//  The first check only finds out where the server's changes are up to
+fn check_for_changes(
+    listeners: &Arc<Mutex<Vec<(TelegramChannel, String)>>>,
+    since: Option<u64>,
+    send: &dyn Fn(&TelegramChannel, &str),
+) -> Option<u64> {
+    let latest = match get_changes(&FoodQuery::parse(""), None) {
+        Some(changes) => changes.latest,
+        None => return since,
+    };
+    let since = match since {
+        // The server restarted, so it's numbering changes from the start again
+        Some(since) if since > latest => 0,
+        Some(since) => since,
+        None => return Some(latest),
+    };
+    if since == latest {
+        return Some(latest);
+    }
+
     listeners
         .lock()
         .unwrap()
         .to_vec()
         .into_iter()
         .for_each(|(channel, food)| {
-            println!("Checking on {:?} for {}", channel, food);
-            let response = check_food(food);
-
-            channel.send_message(&response, telegram_api);
+            match get_changes(&FoodQuery::parse(&food), Some(since)) {
+                Some(changes) => changes
+                    .changes
+                    .iter()
+                    // Anything newer will be picked up by the next check
+                    .filter(|change| change.id < latest)
+                    .for_each(|change| send(&channel, &change.describe())),
+                None => println!("Couldn't check for changes to {}", food),
+            }
         });
+
+    Some(latest)
 }
 
 fn main() {
@@ -253,26 +788,48 @@ fn main() {
 
     let telegram_token = load_telegram_token();
 
+    let mut core = Core::new().unwrap();
+    let handle = core.handle();
+
+    let api = Api::configure(telegram_token.clone())
+        .build(handle)
+        .unwrap();
+
+    let (outbox, outgoing) = mpsc::unbounded();
+    let outgoing_api = api.clone();
+    let outgoing = outgoing.for_each(move |(channel, message): (TelegramChannel, String)| {
+        channel.send_message(&message, &outgoing_api);
+        Ok(())
+    });
+
     // Listeners loop
-    /*let listeners_clone = Arc::clone(&listeners);
-    let telegram_token_clone = telegram_token.clone();
+    let listeners_clone = Arc::clone(&listeners);
+    let digest_outbox = outbox.clone();
     thread::spawn(move || {
         let listeners = listeners_clone;
         loop {
-            let telegram_token = telegram_token_clone.clone();
             println!("Seconds till scheduled: {:?}", get_time_till_scheduled());
             thread::sleep(get_time_till_scheduled());
             println!("Checking for foods now!");
-            check_for_foods(&listeners, &api);
+            check_for_foods(&listeners, &|channel, message| {
+                send_from_thread(&digest_outbox, channel, message)
+            });
         }
-    });*/
-
-    let mut core = Core::new().unwrap();
-    let handle = core.handle();
+    });
 
-    let api = Api::configure(telegram_token.clone())
-        .build(handle)
-        .unwrap();
+    // Menu changes loop
+    let changes_listeners = Arc::clone(&listeners);
+    let changes_outbox = outbox.clone();
+    thread::spawn(move || {
+        let mut last_checked = None;
+        loop {
+            last_checked =
+                check_for_changes(&changes_listeners, last_checked, &|channel, message| {
+                    send_from_thread(&changes_outbox, channel, message)
+                });
+            thread::sleep(Duration::from_secs(60 * MINUTES_BETWEEN_CHANGE_CHECKS));
+        }
+    });
 
     let stream = api.stream().then(|mb_update| {
         let res: Result<Result<Update, Error>, ()> = Ok(mb_update);
@@ -307,5 +864,5 @@ fn main() {
         Ok(())
     });
 
-    core.run(future).unwrap();
+    core.run(future.join(outgoing)).unwrap();
 }